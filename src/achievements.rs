@@ -0,0 +1,262 @@
+//! Goals that carry over between runs. Progress comes from the playfield's
+//! `Event`s and is kept in the save, under `achievement.<id>` in both the
+//! unlocks and the stats.
+
+use std::collections::VecDeque;
+
+use macroquad::prelude::*;
+
+use crate::leaderboard::format_duration;
+use crate::playfield::{BIG_ENEMY_SIZE, Event, Playfield};
+use crate::save::SaveData;
+use crate::stats::RunTracker;
+
+const TOAST_DURATION: f32 = 3.0;
+/// Time taken to slide a toast in or out.
+const TOAST_SLIDE: f32 = 0.25;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Achievement {
+    HighScorer,
+    BigGameHunter,
+    Pacifist,
+    Untouchable,
+}
+
+impl Achievement {
+    pub const ALL: [Achievement; 4] = [
+        Achievement::HighScorer,
+        Achievement::BigGameHunter,
+        Achievement::Pacifist,
+        Achievement::Untouchable,
+    ];
+
+    fn id(self) -> &'static str {
+        match self {
+            Achievement::HighScorer => "high_scorer",
+            Achievement::BigGameHunter => "big_game_hunter",
+            Achievement::Pacifist => "pacifist",
+            Achievement::Untouchable => "untouchable",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Achievement::HighScorer => "Five Digits",
+            Achievement::BigGameHunter => "Big Game Hunter",
+            Achievement::Pacifist => "Conscientious Objector",
+            Achievement::Untouchable => "Untouchable",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Achievement::HighScorer => "Score 10000 points in one run",
+            Achievement::BigGameHunter => "Destroy 100 big enemies",
+            Achievement::Pacifist => "Survive 5 minutes without firing",
+            Achievement::Untouchable => "Beat a boss without getting hit",
+        }
+    }
+
+    /// Progress needed to unlock.
+    fn goal(self) -> u64 {
+        match self {
+            Achievement::HighScorer => 10_000,
+            Achievement::BigGameHunter => 100,
+            Achievement::Pacifist => 5 * 60,
+            Achievement::Untouchable => 1,
+        }
+    }
+
+    /// Whether progress adds up over every run, rather than being the best
+    /// single run.
+    fn cumulative(self) -> bool {
+        matches!(self, Achievement::BigGameHunter)
+    }
+
+    fn key(self) -> String {
+        format!("achievement.{}", self.id())
+    }
+
+    pub fn unlocked(self, save: &SaveData) -> bool {
+        save.unlocks.contains(&self.key())
+    }
+
+    pub fn progress(self, save: &SaveData) -> u64 {
+        save.stats.get(&self.key()).copied().unwrap_or(0)
+    }
+
+    /// Progress towards the goal, e.g. `37 / 100`.
+    fn format_progress(self, progress: u64) -> String {
+        let progress = progress.min(self.goal());
+        match self {
+            Achievement::Pacifist => format!(
+                "{} / {}",
+                format_duration(progress as f32),
+                format_duration(self.goal() as f32)
+            ),
+            _ => format!("{progress} / {}", self.goal()),
+        }
+    }
+}
+
+/// Keeps the toasts for fresh unlocks.
+pub struct Achievements {
+    /// Unlocks still to show, with the time left on screen for the first.
+    toasts: VecDeque<(Achievement, f32)>,
+    /// Whether anyone was hit since the current boss showed up.
+    hit_since_boss: bool,
+}
+
+impl Achievements {
+    pub fn new() -> Achievements {
+        Achievements {
+            toasts: VecDeque::new(),
+            hit_since_boss: false,
+        }
+    }
+
+    /// Updates progress with the `events` of the ticks simulated since the
+    /// last call and the run so far. Returns whether anything was unlocked,
+    /// in which case the save should be written.
+    pub fn update(
+        &mut self,
+        field: &Playfield,
+        events: &[Event],
+        run: &RunTracker,
+        save: &mut SaveData,
+    ) -> bool {
+        let big_kills = events
+            .iter()
+            .filter(
+                |event| matches!(event, Event::EnemyKilled { size, .. } if *size > BIG_ENEMY_SIZE),
+            )
+            .count() as u64;
+        let held_seconds = run
+            .players
+            .iter()
+            .filter(|stats| stats.shots == 0)
+            .map(|stats| stats.time as u64)
+            .max()
+            .unwrap_or(0);
+
+        let mut flawless_bosses = 0;
+        for event in events {
+            match event {
+                Event::BossSpawned => self.hit_since_boss = false,
+                Event::PlayerHit { .. } => self.hit_since_boss = true,
+                Event::BossDefeated if !self.hit_since_boss => flawless_bosses += 1,
+                _ => {}
+            }
+        }
+
+        let mut unlocked = false;
+        unlocked |= self.advance(save, Achievement::HighScorer, field.best_score().into());
+        unlocked |= self.advance(save, Achievement::BigGameHunter, big_kills);
+        unlocked |= self.advance(save, Achievement::Pacifist, held_seconds);
+        unlocked |= self.advance(save, Achievement::Untouchable, flawless_bosses);
+        unlocked
+    }
+
+    /// Adds `value` to cumulative progress, or raises the best run to it.
+    /// Returns whether that unlocked the achievement.
+    fn advance(&mut self, save: &mut SaveData, achievement: Achievement, value: u64) -> bool {
+        let key = achievement.key();
+        let progress = save.stats.entry(key.clone()).or_default();
+        *progress = if achievement.cumulative() {
+            *progress + value
+        } else {
+            (*progress).max(value)
+        };
+        if *progress >= achievement.goal() && save.unlocks.insert(key) {
+            self.toasts.push_back((achievement, TOAST_DURATION));
+            return true;
+        }
+        false
+    }
+
+    /// Draws the oldest unlock at the top of the screen, over the HUD.
+    pub fn draw_toasts(&mut self, font: &Font) {
+        let Some((achievement, time_left)) = self.toasts.front_mut() else {
+            return;
+        };
+        *time_left -= get_frame_time();
+        let (achievement, time_left) = (*achievement, *time_left);
+        if time_left <= 0.0 {
+            self.toasts.pop_front();
+            return;
+        }
+
+        let size = vec2(380.0, 64.0);
+        let shown = ((TOAST_DURATION - time_left).min(time_left) / TOAST_SLIDE).min(1.0);
+        let x = screen_width() / 2.0 - size.x / 2.0;
+        let y = -size.y + shown * (size.y + 10.0);
+        draw_rectangle(x, y, size.x, size.y, Color::new(0.0, 0.0, 0.0, 0.8));
+        draw_rectangle_lines(x, y, size.x, size.y, 2.0, YELLOW);
+        let text = |text: &str, y: f32, font_size: u16, color: Color| {
+            let dimensions = measure_text(text, Some(font), font_size, 1.0);
+            draw_text_ex(
+                text,
+                screen_width() / 2.0 - dimensions.width / 2.0,
+                y,
+                TextParams {
+                    font: Some(font),
+                    font_size,
+                    color,
+                    ..Default::default()
+                },
+            );
+        };
+        text("ACHIEVEMENT UNLOCKED", y + 22.0, 12, YELLOW);
+        text(&achievement.name().to_uppercase(), y + 48.0, 18, WHITE);
+    }
+}
+
+/// Lists every achievement with its progress, centred on screen.
+pub fn draw(save: &SaveData, font: &Font) {
+    let left = (screen_width() - 760.0).max(0.0) / 2.0;
+    let top = screen_height() / 2.0 - 120.0;
+    let text = |text: &str, x: f32, y: f32, font_size: u16, color: Color| {
+        draw_text_ex(
+            text,
+            x,
+            y,
+            TextParams {
+                font: Some(font),
+                font_size,
+                color,
+                ..Default::default()
+            },
+        );
+    };
+
+    let title = "ACHIEVEMENTS";
+    let title_dimensions = measure_text(title, Some(font), 40, 1.0);
+    text(
+        title,
+        screen_width() / 2.0 - title_dimensions.width / 2.0,
+        top - 40.0,
+        40,
+        WHITE,
+    );
+    for (i, achievement) in Achievement::ALL.into_iter().enumerate() {
+        let y = top + 20.0 + i as f32 * 64.0;
+        let (color, status) = if achievement.unlocked(save) {
+            (YELLOW, "UNLOCKED".to_string())
+        } else {
+            (
+                WHITE,
+                achievement.format_progress(achievement.progress(save)),
+            )
+        };
+        text(&achievement.name().to_uppercase(), left, y, 18, color);
+        text(
+            &achievement.description().to_uppercase(),
+            left,
+            y + 24.0,
+            12,
+            GRAY,
+        );
+        text(&status, left + 580.0, y, 14, color);
+    }
+}
@@ -0,0 +1,233 @@
+//! Stand-in for the online leaderboard, to develop and test the game's
+//! client against without a real server. Every run submitted is appended
+//! to one INI file, replay included, and boards are sorted on request.
+//!
+//! ```text
+//! cargo run --bin leaderboard_server -- [--port 8787] [--file scores.ini]
+//! ```
+//!
+//! then point the game at it in `config.ini`:
+//!
+//! ```text
+//! [online]
+//! server = http://127.0.0.1:8787
+//! ```
+
+use std::fs::OpenOptions;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[path = "../ini.rs"]
+mod ini;
+
+const DEFAULT_PORT: u16 = 8787;
+const DEFAULT_FILE: &str = "scores.ini";
+/// Most runs returned for one board.
+const MAX_LIMIT: usize = 100;
+/// Replays of long runs get big, but not this big.
+const MAX_BODY: usize = 8 * 1024 * 1024;
+/// Fields of a run stored and handed out, besides the board and replay.
+const FIELDS: [&str; 6] = ["name", "score", "date", "duration", "seed", "ship"];
+
+struct Run {
+    board: String,
+    score: u32,
+    /// The `FIELDS` as `key = value` lines.
+    fields: String,
+}
+
+impl Run {
+    fn from_section(section: &ini::Section) -> Option<Run> {
+        let board = section.get("board")?;
+        if !valid_board(board) {
+            return None;
+        }
+        Some(Run {
+            board: board.to_string(),
+            score: section.parse("score")?,
+            fields: fields(section),
+        })
+    }
+}
+
+/// The `FIELDS` of `section` as `key = value` lines.
+fn fields(section: &ini::Section) -> String {
+    FIELDS
+        .iter()
+        .map(|key| format!("{key} = {}\n", section.get(key).unwrap_or_default()))
+        .collect()
+}
+
+fn valid_board(board: &str) -> bool {
+    !board.is_empty()
+        && board
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+}
+
+struct Server {
+    file: PathBuf,
+    runs: Vec<Run>,
+}
+
+impl Server {
+    fn open(file: PathBuf) -> io::Result<Server> {
+        let text = match std::fs::read_to_string(&file) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(err),
+        };
+        let runs = ini::parse(&text)
+            .iter()
+            .filter(|section| section.name == "entry")
+            .filter_map(Run::from_section)
+            .collect();
+        Ok(Server { file, runs })
+    }
+
+    /// The best `limit` runs on `board` as `[entry]` sections.
+    fn top(&self, board: &str, limit: usize) -> String {
+        let mut runs: Vec<&Run> = self.runs.iter().filter(|run| run.board == board).collect();
+        runs.sort_by_key(|run| std::cmp::Reverse(run.score));
+        runs.iter()
+            .take(limit)
+            .map(|run| format!("[entry]\n{}\n", run.fields))
+            .collect()
+    }
+
+    /// Stores the `[entry]` in `body` on `board`, replay and all.
+    fn submit(&mut self, board: &str, body: &str) -> Result<(), String> {
+        let sections = ini::parse(body);
+        let section = sections
+            .iter()
+            .find(|section| section.name == "entry")
+            .ok_or("no [entry] section")?;
+        section.parse::<u32>("score").ok_or("missing score")?;
+        section.parse::<u64>("seed").ok_or("missing seed")?;
+        let replay = section
+            .get("replay")
+            .filter(|replay| !replay.is_empty())
+            .ok_or("missing replay")?;
+
+        let stored = format!(
+            "[entry]\nboard = {board}\n{}replay = {replay}\n\n",
+            fields(section)
+        );
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.file)
+            .and_then(|mut file| file.write_all(stored.as_bytes()))
+            .map_err(|err| format!("could not store the run: {err}"))?;
+
+        let run = Run::from_section(&ini::parse(&stored)[0]).ok_or("invalid run")?;
+        println!(
+            "{board}: {} by {}",
+            run.score,
+            section.get("name").unwrap_or_default()
+        );
+        self.runs.push(run);
+        Ok(())
+    }
+
+    fn handle(&mut self, stream: TcpStream) -> io::Result<()> {
+        stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+        let mut reader = BufReader::new(&stream);
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line)?;
+        let mut content_length = 0;
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':')
+                && name.eq_ignore_ascii_case("content-length")
+            {
+                content_length = value.trim().parse().unwrap_or(0);
+            }
+        }
+        if content_length > MAX_BODY {
+            return respond(&stream, "413 Payload Too Large", "run too big\n");
+        }
+        let mut body = vec![0; content_length];
+        reader.read_exact(&mut body)?;
+        let body = String::from_utf8_lossy(&body);
+
+        let mut parts = request_line.split_whitespace();
+        let (method, target) = (
+            parts.next().unwrap_or_default(),
+            parts.next().unwrap_or_default(),
+        );
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        let Some(board) = path
+            .strip_prefix("/boards/")
+            .filter(|board| valid_board(board))
+        else {
+            return respond(&stream, "404 Not Found", "no such board\n");
+        };
+        match method {
+            "GET" => {
+                let limit = query
+                    .split('&')
+                    .find_map(|pair| pair.strip_prefix("limit="))
+                    .and_then(|limit| limit.parse().ok())
+                    .unwrap_or(MAX_LIMIT)
+                    .min(MAX_LIMIT);
+                respond(&stream, "200 OK", &self.top(board, limit))
+            }
+            "POST" => match self.submit(board, &body) {
+                Ok(()) => respond(&stream, "201 Created", ""),
+                Err(err) => respond(&stream, "400 Bad Request", &format!("{err}\n")),
+            },
+            _ => respond(&stream, "405 Method Not Allowed", ""),
+        }
+    }
+}
+
+fn respond(mut stream: &TcpStream, status: &str, body: &str) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.0 {status}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )
+}
+
+fn main() -> io::Result<()> {
+    let mut port = DEFAULT_PORT;
+    let mut file = PathBuf::from(DEFAULT_FILE);
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match (arg.as_str(), args.next()) {
+            ("--port", Some(value)) => {
+                port = value
+                    .parse()
+                    .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "invalid port"))?;
+            }
+            ("--file", Some(value)) => file = PathBuf::from(value),
+            _ => {
+                eprintln!("usage: leaderboard_server [--port <port>] [--file <scores.ini>]");
+                std::process::exit(2);
+            }
+        }
+    }
+
+    let mut server = Server::open(file)?;
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+    println!(
+        "Serving {} runs from {} on http://127.0.0.1:{port}",
+        server.runs.len(),
+        server.file.display()
+    );
+    // One client at a time keeps the file consistent, and is plenty for
+    // testing
+    for stream in listener.incoming() {
+        let result = stream.and_then(|stream| server.handle(stream));
+        if let Err(err) = result {
+            eprintln!("{err}");
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,517 @@
+//! Action mapping. Game code asks about `Action`s and `Bindings` decides
+//! which keys trigger them, so players can rebind the keyboard. Gamepad
+//! buttons stay fixed. Bindings are stored as sections of the save file.
+
+use std::fmt::Write;
+
+use macroquad::prelude::*;
+
+use crate::gamepad::{Gamepads, PadButton};
+use crate::ini::Section;
+use crate::input::{Button, PlayerInput};
+use crate::player::MAX_PLAYERS;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    Fire,
+    Bomb,
+    Dash,
+    Pause,
+    Confirm,
+    Back,
+    MenuUp,
+    MenuDown,
+}
+
+impl Action {
+    /// Ship actions, bound separately for every player.
+    pub const SHIP: [Action; 7] = [
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::MoveUp,
+        Action::MoveDown,
+        Action::Fire,
+        Action::Bomb,
+        Action::Dash,
+    ];
+    /// Actions shared by everyone.
+    pub const GLOBAL: [Action; 5] = [
+        Action::Pause,
+        Action::Confirm,
+        Action::Back,
+        Action::MenuUp,
+        Action::MenuDown,
+    ];
+
+    /// Key used in the bindings file.
+    pub fn id(self) -> &'static str {
+        match self {
+            Action::MoveLeft => "move_left",
+            Action::MoveRight => "move_right",
+            Action::MoveUp => "move_up",
+            Action::MoveDown => "move_down",
+            Action::Fire => "fire",
+            Action::Bomb => "bomb",
+            Action::Dash => "dash",
+            Action::Pause => "pause",
+            Action::Confirm => "confirm",
+            Action::Back => "back",
+            Action::MenuUp => "menu_up",
+            Action::MenuDown => "menu_down",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::MoveLeft => "Move Left",
+            Action::MoveRight => "Move Right",
+            Action::MoveUp => "Move Up",
+            Action::MoveDown => "Move Down",
+            Action::Fire => "Fire",
+            Action::Bomb => "Bomb",
+            Action::Dash => "Dash",
+            Action::Pause => "Pause",
+            Action::Confirm => "Confirm",
+            Action::Back => "Back",
+            Action::MenuUp => "Menu Up",
+            Action::MenuDown => "Menu Down",
+        }
+    }
+
+    /// Menu actions are never checked during play, so they may share keys
+    /// with the ship controls.
+    fn in_menus(self) -> bool {
+        matches!(
+            self,
+            Action::Confirm | Action::Back | Action::MenuUp | Action::MenuDown
+        )
+    }
+
+    /// Fixed gamepad button for the action. Movement and menu navigation go
+    /// through the stick and d-pad instead.
+    pub fn pad_button(self) -> Option<PadButton> {
+        match self {
+            Action::Fire | Action::Confirm => Some(PadButton::South),
+            Action::Bomb | Action::Back => Some(PadButton::East),
+            Action::Dash => Some(PadButton::West),
+            Action::Pause => Some(PadButton::Start),
+            Action::MoveLeft
+            | Action::MoveRight
+            | Action::MoveUp
+            | Action::MoveDown
+            | Action::MenuUp
+            | Action::MenuDown => None,
+        }
+    }
+}
+
+/// How the first player steers their ship.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ControlScheme {
+    Keyboard,
+    /// The ship eases towards the cursor and the left button fires.
+    Mouse,
+    /// Like `Mouse`, but always firing.
+    MouseAutofire,
+}
+
+impl ControlScheme {
+    pub const ALL: [ControlScheme; 3] = [
+        ControlScheme::Keyboard,
+        ControlScheme::Mouse,
+        ControlScheme::MouseAutofire,
+    ];
+
+    fn id(self) -> &'static str {
+        match self {
+            ControlScheme::Keyboard => "keyboard",
+            ControlScheme::Mouse => "mouse",
+            ControlScheme::MouseAutofire => "mouse_autofire",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ControlScheme::Keyboard => "Keyboard",
+            ControlScheme::Mouse => "Mouse",
+            ControlScheme::MouseAutofire => "Mouse + Autofire",
+        }
+    }
+
+    /// The scheme after this one, wrapping around.
+    pub fn next(self) -> ControlScheme {
+        let index = ControlScheme::ALL.iter().position(|scheme| *scheme == self);
+        ControlScheme::ALL[(index.unwrap() + 1) % ControlScheme::ALL.len()]
+    }
+
+    /// The scheme before this one, wrapping around.
+    pub fn previous(self) -> ControlScheme {
+        let index = ControlScheme::ALL.iter().position(|scheme| *scheme == self);
+        let len = ControlScheme::ALL.len();
+        ControlScheme::ALL[(index.unwrap() + len - 1) % len]
+    }
+}
+
+/// Who a binding belongs to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scope {
+    Player(usize),
+    Global,
+}
+
+impl Scope {
+    fn section(self) -> String {
+        match self {
+            Scope::Player(slot) => format!("player{}", slot + 1),
+            Scope::Global => "global".to_string(),
+        }
+    }
+
+    fn actions(self) -> &'static [Action] {
+        match self {
+            Scope::Player(_) => &Action::SHIP,
+            Scope::Global => &Action::GLOBAL,
+        }
+    }
+
+    pub fn all() -> impl Iterator<Item = Scope> {
+        (0..MAX_PLAYERS).map(Scope::Player).chain([Scope::Global])
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Bindings {
+    ship: [[KeyCode; Action::SHIP.len()]; MAX_PLAYERS],
+    global: [KeyCode; Action::GLOBAL.len()],
+    pub scheme: ControlScheme,
+}
+
+impl Default for Bindings {
+    /// Arrows + Space for the first player, WASD + F for the second.
+    fn default() -> Bindings {
+        Bindings {
+            ship: [
+                [
+                    KeyCode::Left,
+                    KeyCode::Right,
+                    KeyCode::Up,
+                    KeyCode::Down,
+                    KeyCode::Space,
+                    KeyCode::B,
+                    KeyCode::LeftShift,
+                ],
+                [
+                    KeyCode::A,
+                    KeyCode::D,
+                    KeyCode::W,
+                    KeyCode::S,
+                    KeyCode::F,
+                    KeyCode::G,
+                    KeyCode::H,
+                ],
+            ],
+            global: [
+                KeyCode::Escape,
+                KeyCode::Space,
+                KeyCode::Escape,
+                KeyCode::Up,
+                KeyCode::Down,
+            ],
+            scheme: ControlScheme::Keyboard,
+        }
+    }
+}
+
+impl Bindings {
+    /// Reads the binding sections out of `sections`, ignoring any others.
+    /// Bindings missing there keep their defaults.
+    pub fn from_sections(sections: &[Section]) -> Result<Bindings, String> {
+        let mut bindings = Bindings::default();
+        for section in sections {
+            if section.name == "options" {
+                if let Some(id) = section.get("scheme") {
+                    bindings.scheme = ControlScheme::ALL
+                        .into_iter()
+                        .find(|scheme| scheme.id() == id)
+                        .ok_or_else(|| format!("unknown control scheme {id}"))?;
+                }
+                continue;
+            }
+            let Some(scope) = Scope::all().find(|scope| scope.section() == section.name) else {
+                continue;
+            };
+            for (id, value) in &section.entries {
+                let action = scope
+                    .actions()
+                    .iter()
+                    .find(|action| action.id() == id)
+                    .ok_or_else(|| format!("unknown action {id} in [{}]", section.name))?;
+                let key = parse_key(value).ok_or_else(|| format!("unknown key {value}"))?;
+                bindings.set(scope, *action, key);
+            }
+        }
+        Ok(bindings)
+    }
+
+    pub fn to_ini(&self) -> String {
+        let mut text = String::new();
+        writeln!(text, "[options]\nscheme = {}\n", self.scheme.id()).unwrap();
+        for scope in Scope::all() {
+            writeln!(text, "[{}]", scope.section()).unwrap();
+            for action in scope.actions() {
+                writeln!(text, "{} = {:?}", action.id(), self.key(scope, *action)).unwrap();
+            }
+            text.push('\n');
+        }
+        text
+    }
+
+    fn index(scope: Scope, action: Action) -> usize {
+        scope
+            .actions()
+            .iter()
+            .position(|other| *other == action)
+            .expect("action belongs to another scope")
+    }
+
+    pub fn key(&self, scope: Scope, action: Action) -> KeyCode {
+        let index = Bindings::index(scope, action);
+        match scope {
+            Scope::Player(slot) => self.ship[slot][index],
+            Scope::Global => self.global[index],
+        }
+    }
+
+    pub fn set(&mut self, scope: Scope, action: Action, key: KeyCode) {
+        let index = Bindings::index(scope, action);
+        match scope {
+            Scope::Player(slot) => self.ship[slot][index] = key,
+            Scope::Global => self.global[index] = key,
+        }
+    }
+
+    /// Bindings that share a key with another one checked at the same time.
+    pub fn conflicts(&self) -> Vec<(Scope, Action)> {
+        let bindings: Vec<(Scope, Action)> = Scope::all()
+            .flat_map(|scope| scope.actions().iter().map(move |action| (scope, *action)))
+            .collect();
+        bindings
+            .iter()
+            .filter(|(scope, action)| {
+                bindings.iter().any(|(other_scope, other)| {
+                    (scope, action) != (other_scope, other)
+                        && action.in_menus() == other.in_menus()
+                        && self.key(*scope, *action) == self.key(*other_scope, *other)
+                })
+            })
+            .copied()
+            .collect()
+    }
+
+    /// Ship controls for `slot` from its keys and gamepad together.
+    pub fn input(&self, slot: usize, gamepads: &Gamepads) -> PlayerInput {
+        let mut input = PlayerInput::default();
+        for (action, button) in Action::SHIP.into_iter().zip([
+            Button::Left,
+            Button::Right,
+            Button::Up,
+            Button::Down,
+            Button::Fire,
+            Button::Bomb,
+            Button::Dash,
+        ]) {
+            input.set(button, is_key_down(self.key(Scope::Player(slot), action)));
+        }
+        input.merge(gamepads.input(slot))
+    }
+
+    /// Pressed this frame by `slot`, on its keys or its gamepad.
+    pub fn player_pressed(&self, slot: usize, action: Action, gamepads: &Gamepads) -> bool {
+        is_key_pressed(self.key(Scope::Player(slot), action))
+            || action
+                .pad_button()
+                .is_some_and(|button| gamepads.pressed(slot, button))
+    }
+
+    /// A global action pressed this frame on the keyboard or any gamepad.
+    pub fn pressed(&self, action: Action, gamepads: &Gamepads) -> bool {
+        is_key_pressed(self.key(Scope::Global, action))
+            || action
+                .pad_button()
+                .is_some_and(|button| gamepads.any_pressed(button))
+    }
+
+    /// Moves `selection` through `count` menu entries with the menu keys,
+    /// the d-pad or a stick. Returns true when the selected entry is
+    /// confirmed.
+    pub fn navigate_menu(&self, selection: &mut usize, count: usize, gamepads: &Gamepads) -> bool {
+        let mut step = gamepads.menu_direction().y;
+        if self.pressed(Action::MenuUp, gamepads) {
+            step -= 1;
+        }
+        if self.pressed(Action::MenuDown, gamepads) {
+            step += 1;
+        }
+        *selection = (*selection as i32 + step).rem_euclid(count as i32) as usize;
+        self.pressed(Action::Confirm, gamepads)
+    }
+
+    /// Left or right step for menu entries that hold a value: -1, 1 or 0.
+    /// Uses player one's movement keys, the d-pad or a stick.
+    pub fn menu_sideways(&self, gamepads: &Gamepads) -> i32 {
+        let mut step = gamepads.menu_direction().x;
+        if is_key_pressed(self.key(Scope::Player(0), Action::MoveLeft)) {
+            step -= 1;
+        }
+        if is_key_pressed(self.key(Scope::Player(0), Action::MoveRight)) {
+            step += 1;
+        }
+        step
+    }
+}
+
+/// Keys that can be bound, by their `Debug` names.
+const BINDABLE_KEYS: [KeyCode; 80] = [
+    KeyCode::Space,
+    KeyCode::Apostrophe,
+    KeyCode::Comma,
+    KeyCode::Minus,
+    KeyCode::Period,
+    KeyCode::Slash,
+    KeyCode::Key0,
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+    KeyCode::Semicolon,
+    KeyCode::Equal,
+    KeyCode::A,
+    KeyCode::B,
+    KeyCode::C,
+    KeyCode::D,
+    KeyCode::E,
+    KeyCode::F,
+    KeyCode::G,
+    KeyCode::H,
+    KeyCode::I,
+    KeyCode::J,
+    KeyCode::K,
+    KeyCode::L,
+    KeyCode::M,
+    KeyCode::N,
+    KeyCode::O,
+    KeyCode::P,
+    KeyCode::Q,
+    KeyCode::R,
+    KeyCode::S,
+    KeyCode::T,
+    KeyCode::U,
+    KeyCode::V,
+    KeyCode::W,
+    KeyCode::X,
+    KeyCode::Y,
+    KeyCode::Z,
+    KeyCode::LeftBracket,
+    KeyCode::Backslash,
+    KeyCode::RightBracket,
+    KeyCode::GraveAccent,
+    KeyCode::Escape,
+    KeyCode::Enter,
+    KeyCode::Tab,
+    KeyCode::Backspace,
+    KeyCode::Insert,
+    KeyCode::Delete,
+    KeyCode::Right,
+    KeyCode::Left,
+    KeyCode::Down,
+    KeyCode::Up,
+    KeyCode::PageUp,
+    KeyCode::PageDown,
+    KeyCode::Home,
+    KeyCode::End,
+    KeyCode::Kp0,
+    KeyCode::Kp1,
+    KeyCode::Kp2,
+    KeyCode::Kp3,
+    KeyCode::Kp4,
+    KeyCode::Kp5,
+    KeyCode::Kp6,
+    KeyCode::Kp7,
+    KeyCode::Kp8,
+    KeyCode::Kp9,
+    KeyCode::KpEnter,
+    KeyCode::LeftShift,
+    KeyCode::LeftControl,
+    KeyCode::LeftAlt,
+    KeyCode::RightShift,
+    KeyCode::RightControl,
+    KeyCode::RightAlt,
+    KeyCode::Menu,
+];
+
+pub fn bindable(key: KeyCode) -> bool {
+    BINDABLE_KEYS.contains(&key)
+}
+
+fn parse_key(name: &str) -> Option<KeyCode> {
+    BINDABLE_KEYS
+        .into_iter()
+        .find(|key| format!("{key:?}") == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_only_share_keys_across_play_and_menus() {
+        // Space fires and confirms, Escape pauses and goes back
+        assert!(Bindings::default().conflicts().is_empty());
+    }
+
+    #[test]
+    fn conflicts_flag_both_sides() {
+        let mut bindings = Bindings::default();
+        bindings.set(Scope::Player(1), Action::Fire, KeyCode::Space);
+        assert_eq!(
+            bindings.conflicts(),
+            [
+                (Scope::Player(0), Action::Fire),
+                (Scope::Player(1), Action::Fire)
+            ]
+        );
+
+        // Pause is checked during play too
+        let mut bindings = Bindings::default();
+        bindings.set(Scope::Global, Action::Pause, KeyCode::B);
+        assert_eq!(
+            bindings.conflicts(),
+            [
+                (Scope::Player(0), Action::Bomb),
+                (Scope::Global, Action::Pause)
+            ]
+        );
+    }
+
+    #[test]
+    fn menu_actions_conflict_among_themselves() {
+        let mut bindings = Bindings::default();
+        bindings.set(Scope::Global, Action::MenuDown, KeyCode::Up);
+        assert_eq!(
+            bindings.conflicts(),
+            [
+                (Scope::Global, Action::MenuUp),
+                (Scope::Global, Action::MenuDown)
+            ]
+        );
+    }
+}
@@ -0,0 +1,332 @@
+//! Campaign levels: a few scripted waves, then a boss. The levels live in
+//! `assets/levels.ini` like the shop inventory. Clearing a level unlocks the
+//! next one, and the best star rating of each is kept in the save.
+
+use macroquad::prelude::*;
+
+use crate::ini::{self, Section};
+use crate::leaderboard::format_duration;
+use crate::playfield::TICK;
+use crate::save::SaveData;
+
+/// One wave of a level.
+#[derive(Clone, Copy, Debug)]
+pub struct WaveScript {
+    pub count: u32,
+    /// Smallest and largest enemy size.
+    pub size: (f32, f32),
+    /// Slowest and fastest fall speed.
+    pub speed: (f32, f32),
+    /// Ticks between spawns.
+    pub interval: u32,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct BossScript {
+    pub size: f32,
+    pub health: u32,
+    pub speed: f32,
+    /// Ticks between the enemies the boss sends down, or 0 for none.
+    pub escort_interval: u32,
+}
+
+#[derive(Clone, Debug)]
+pub struct Level {
+    pub name: String,
+    /// Multiplied into the starfield's colours.
+    pub tint: Vec3,
+    /// Seconds of play to beat for the third star.
+    pub par_time: f32,
+    pub waves: Vec<WaveScript>,
+    pub boss: BossScript,
+}
+
+/// Two numbers separated by whitespace, or `default`.
+fn pair(section: &Section, key: &str, default: (f32, f32)) -> (f32, f32) {
+    let values: Vec<f32> = section
+        .get(key)
+        .unwrap_or_default()
+        .split_whitespace()
+        .filter_map(|value| value.parse().ok())
+        .collect();
+    match values[..] {
+        [low, high] => (low.min(high), low.max(high)),
+        _ => default,
+    }
+}
+
+fn ticks(seconds: f32) -> u32 {
+    (seconds / TICK).round() as u32
+}
+
+/// Reads the levels. Each `[level]` takes the `[wave]` and `[boss]`
+/// sections after it; like the shop, missing keys fall back to defaults and
+/// stray sections are skipped.
+pub fn parse_levels(text: &str) -> Vec<Level> {
+    let mut levels: Vec<Level> = vec![];
+    for section in ini::parse(text) {
+        if section.name == "level" {
+            let tint: Vec<f32> = section
+                .get("tint")
+                .unwrap_or_default()
+                .split_whitespace()
+                .filter_map(|value| value.parse().ok())
+                .collect();
+            levels.push(Level {
+                name: section
+                    .get("name")
+                    .map_or_else(|| format!("Level {}", levels.len() + 1), str::to_string),
+                tint: match tint[..] {
+                    [red, green, blue] => vec3(red, green, blue),
+                    _ => Vec3::ONE,
+                },
+                par_time: section.parse("par_time").unwrap_or(120.0),
+                waves: vec![],
+                boss: BossScript {
+                    size: 96.0,
+                    health: 200,
+                    speed: 40.0,
+                    escort_interval: 0,
+                },
+            });
+            continue;
+        }
+        let Some(level) = levels.last_mut() else {
+            continue;
+        };
+        match section.name.as_str() {
+            "wave" => level.waves.push(WaveScript {
+                count: section.parse("count").unwrap_or(10),
+                size: pair(&section, "size", (16.0, 64.0)),
+                speed: pair(&section, "speed", (50.0, 150.0)),
+                interval: ticks(section.parse("interval").unwrap_or(0.5)).max(1),
+            }),
+            "boss" => {
+                level.boss = BossScript {
+                    size: section.parse("size").unwrap_or(96.0),
+                    health: section.parse("health").unwrap_or(200),
+                    speed: section.parse("speed").unwrap_or(40.0),
+                    escort_interval: ticks(section.parse("escort_interval").unwrap_or(0.0)),
+                }
+            }
+            _ => {}
+        }
+    }
+    levels
+}
+
+fn stars_key(level: usize) -> String {
+    format!("campaign.stars.{}", level + 1)
+}
+
+fn unlock_key(level: usize) -> String {
+    format!("campaign.level.{}", level + 1)
+}
+
+/// Whether `level`, counted from 0, can be played. The first always can.
+pub fn unlocked(save: &SaveData, level: usize) -> bool {
+    level == 0 || save.unlocks.contains(&unlock_key(level))
+}
+
+/// Best rating of `level`, 0 if it was never cleared.
+pub fn best_stars(save: &SaveData, level: usize) -> u32 {
+    save.stats
+        .get(&stars_key(level))
+        .map_or(0, |stars| *stars as u32)
+}
+
+/// How a cleared level went.
+#[derive(Clone, Copy, Debug)]
+pub struct Rating {
+    pub hits_taken: u32,
+    pub time: f32,
+    pub par_time: f32,
+}
+
+impl Rating {
+    /// One star for the clear, one for taking no hits and one for beating
+    /// the par time.
+    pub fn stars(self) -> u32 {
+        1 + u32::from(self.hits_taken == 0) + u32::from(self.time <= self.par_time)
+    }
+}
+
+/// Keeps the best rating of `level` and unlocks the next one.
+pub fn record_clear(save: &mut SaveData, level: usize, rating: Rating) {
+    let best = save.stats.entry(stars_key(level)).or_default();
+    *best = (*best).max(rating.stars().into());
+    save.unlocks.insert(unlock_key(level + 1));
+}
+
+/// Five-pointed star centred on `center`, filled or outlined.
+fn draw_star(center: Vec2, radius: f32, filled: bool, color: Color) {
+    let point = |i: usize, radius: f32| {
+        let angle = std::f32::consts::PI * (i as f32 / 5.0 - 0.5);
+        center + Vec2::from_angle(angle) * radius
+    };
+    for i in 0..10 {
+        let (outer, inner) = if i % 2 == 0 {
+            (point(i, radius), point(i + 1, radius * 0.4))
+        } else {
+            (point(i + 1, radius), point(i, radius * 0.4))
+        };
+        if filled {
+            draw_triangle(center, outer, inner, color);
+        } else {
+            draw_line(outer.x, outer.y, inner.x, inner.y, 2.0, color);
+        }
+    }
+}
+
+/// The level complete screen, centred.
+pub fn draw_complete(level: &Level, number: usize, rating: Rating, font: &Font) {
+    let text = |text: &str, y: f32, font_size: u16, color: Color| {
+        let dimensions = measure_text(text, Some(font), font_size, 1.0);
+        draw_text_ex(
+            text,
+            screen_width() / 2.0 - dimensions.width / 2.0,
+            y,
+            TextParams {
+                font: Some(font),
+                font_size,
+                color,
+                ..Default::default()
+            },
+        );
+    };
+    let top = screen_height() / 2.0 - 160.0;
+    text(&format!("LEVEL {number} CLEAR"), top, 40, WHITE);
+    text(&level.name.to_uppercase(), top + 40.0, 20, GRAY);
+
+    let stars = rating.stars();
+    for i in 0..3 {
+        let center = vec2(screen_width() / 2.0 + (i as f32 - 1.0) * 80.0, top + 120.0);
+        draw_star(
+            center,
+            32.0,
+            i < stars,
+            if i < stars { YELLOW } else { GRAY },
+        );
+    }
+
+    let goals = [
+        ("CLEARED", true),
+        ("NO HITS TAKEN", rating.hits_taken == 0),
+        (
+            &format!("UNDER {}", format_duration(rating.par_time)) as &str,
+            rating.time <= rating.par_time,
+        ),
+    ];
+    for (i, (goal, met)) in goals.iter().enumerate() {
+        let color = if *met { YELLOW } else { GRAY };
+        text(goal, top + 200.0 + i as f32 * 28.0, 16, color);
+    }
+    text(
+        &format!("TIME {}", format_duration(rating.time)),
+        top + 300.0,
+        16,
+        WHITE,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_keys_fall_back_to_defaults() {
+        let levels = parse_levels(
+            "[wave]\ncount = 3\n\n[level]\n\n[wave]\n\n[level]\nname = Second\ntint = 1 0.5\n",
+        );
+        // The wave before any level is skipped
+        assert_eq!(levels.len(), 2);
+
+        let first = &levels[0];
+        assert_eq!(first.name, "Level 1");
+        assert_eq!(first.tint, Vec3::ONE);
+        assert_eq!(first.par_time, 120.0);
+        assert_eq!(first.waves.len(), 1);
+        let wave = first.waves[0];
+        assert_eq!(wave.count, 10);
+        assert_eq!(wave.size, (16.0, 64.0));
+        assert_eq!(wave.speed, (50.0, 150.0));
+        assert_eq!(wave.interval, ticks(0.5));
+        assert_eq!(first.boss.size, 96.0);
+        assert_eq!(first.boss.health, 200);
+        assert_eq!(first.boss.speed, 40.0);
+        assert_eq!(first.boss.escort_interval, 0);
+
+        let second = &levels[1];
+        assert_eq!(second.name, "Second");
+        // A tint without all three channels is ignored
+        assert_eq!(second.tint, Vec3::ONE);
+        assert!(second.waves.is_empty());
+    }
+
+    #[test]
+    fn pairs_are_put_in_order() {
+        let levels = parse_levels(
+            "[level]\n\n[wave]\nsize = 60 20\nspeed = 80 120\n\n[wave]\nsize = 30\nspeed = fast slow\n",
+        );
+        let waves = &levels[0].waves;
+        assert_eq!(waves[0].size, (20.0, 60.0));
+        assert_eq!(waves[0].speed, (80.0, 120.0));
+        // Anything but two numbers is the default
+        assert_eq!(waves[1].size, (16.0, 64.0));
+        assert_eq!(waves[1].speed, (50.0, 150.0));
+    }
+
+    #[test]
+    fn interval_is_at_least_a_tick() {
+        let levels = parse_levels("[level]\n\n[wave]\ninterval = 0\n\n[wave]\ninterval = 2\n");
+        assert_eq!(levels[0].waves[0].interval, 1);
+        assert_eq!(levels[0].waves[1].interval, ticks(2.0));
+    }
+
+    #[test]
+    fn shipped_levels_parse() {
+        let levels = parse_levels(include_str!("../assets/levels.ini"));
+        assert!(!levels.is_empty());
+        assert!(levels.iter().all(|level| !level.waves.is_empty()));
+    }
+
+    #[test]
+    fn stars() {
+        let rating = |hits_taken, time| Rating {
+            hits_taken,
+            time,
+            par_time: 60.0,
+        };
+        assert_eq!(rating(2, 90.0).stars(), 1);
+        assert_eq!(rating(0, 90.0).stars(), 2);
+        assert_eq!(rating(2, 60.0).stars(), 2);
+        assert_eq!(rating(0, 30.0).stars(), 3);
+    }
+
+    #[test]
+    fn clears_keep_the_best_stars_and_unlock_the_next_level() {
+        let mut save = SaveData::default();
+        assert!(unlocked(&save, 0));
+        assert!(!unlocked(&save, 1));
+        assert_eq!(best_stars(&save, 0), 0);
+
+        let rating = |hits_taken| Rating {
+            hits_taken,
+            time: 90.0,
+            par_time: 60.0,
+        };
+        record_clear(&mut save, 0, rating(0));
+        assert_eq!(best_stars(&save, 0), 2);
+        assert!(unlocked(&save, 1));
+        assert!(!unlocked(&save, 2));
+
+        // A worse clear doesn't take a star away
+        record_clear(&mut save, 0, rating(3));
+        assert_eq!(best_stars(&save, 0), 2);
+        assert_eq!(best_stars(&save, 1), 0);
+
+        record_clear(&mut save, 1, rating(3));
+        assert_eq!(best_stars(&save, 1), 1);
+        assert!(unlocked(&save, 2));
+    }
+}
@@ -0,0 +1,159 @@
+//! Settings, kept apart from the save in a plain INI file in the config
+//! storage so they can be edited by hand or copied between machines.
+
+use std::io;
+
+use crate::bindings::Bindings;
+use crate::ini;
+use crate::persist::Storage;
+use crate::settings::Settings;
+
+pub const CONFIG_FILE: &str = "config.ini";
+/// Bindings file from before the config file, imported on first start.
+const LEGACY_BINDINGS_FILE: &str = "controls.ini";
+
+#[derive(Clone, Debug, Default)]
+pub struct Config {
+    pub bindings: Bindings,
+    /// `http://` URL of the online leaderboard, or empty to keep scores on
+    /// this machine.
+    pub online_server: String,
+    pub settings: Settings,
+}
+
+impl Config {
+    /// Loads the config, or the bindings in `legacy` on the very first
+    /// start. A file that can't be used is reported and replaced by the
+    /// defaults; the next write keeps it as a backup.
+    pub fn load(storage: &mut dyn Storage, legacy: &dyn Storage) -> (Config, Option<String>) {
+        let (text, source, imported) = match storage.read(CONFIG_FILE) {
+            Ok(Some(text)) => (text, CONFIG_FILE, false),
+            Ok(None) => match legacy.read(LEGACY_BINDINGS_FILE) {
+                Ok(Some(text)) => (text, LEGACY_BINDINGS_FILE, true),
+                _ => return (Config::default(), None),
+            },
+            Err(err) => {
+                let message = format!("Could not read {CONFIG_FILE}: {err}");
+                return (Config::default(), Some(message));
+            }
+        };
+
+        match Config::from_text(&text) {
+            Ok(config) if imported => {
+                let message = config
+                    .write(storage)
+                    .err()
+                    .map(|err| format!("Importing old controls: could not save: {err}"));
+                (config, message)
+            }
+            Ok(config) => (config, None),
+            Err(err) => {
+                let message = format!("Ignored {source}: {err}");
+                (Config::default(), Some(message))
+            }
+        }
+    }
+
+    /// Settings in `storage`, or the defaults if there are none that can be
+    /// used. For opening the window, before `load` can report anything.
+    pub fn saved_settings(storage: &dyn Storage) -> Settings {
+        match storage.read(CONFIG_FILE) {
+            Ok(Some(text)) => Config::from_text(&text)
+                .map(|config| config.settings)
+                .unwrap_or_default(),
+            _ => Settings::default(),
+        }
+    }
+
+    pub fn from_text(text: &str) -> Result<Config, String> {
+        let sections = ini::parse(text);
+        let online_server = sections
+            .iter()
+            .filter(|section| section.name == "online")
+            .find_map(|section| section.get("server"))
+            .unwrap_or_default();
+        let settings = match sections.iter().find(|section| section.name == "settings") {
+            Some(section) => Settings::from_section(section)?,
+            None => Settings::default(),
+        };
+        Ok(Config {
+            bindings: Bindings::from_sections(&sections)?,
+            online_server: online_server.to_string(),
+            settings,
+        })
+    }
+
+    pub fn to_text(&self) -> String {
+        format!(
+            "{}{}[online]\nserver = {}\n",
+            self.bindings.to_ini(),
+            self.settings.to_ini(),
+            self.online_server
+        )
+    }
+
+    pub fn write(&self, storage: &mut dyn Storage) -> io::Result<()> {
+        storage.write_with_backup(CONFIG_FILE, &self.to_text())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bindings::{Action, ControlScheme, Scope};
+    use crate::persist::{MemoryStorage, backup_name};
+    use macroquad::input::KeyCode;
+
+    #[test]
+    fn round_trip() {
+        let mut config = Config::default();
+        config
+            .bindings
+            .set(Scope::Player(0), Action::Fire, KeyCode::Z);
+        config.bindings.scheme = ControlScheme::Mouse;
+        config.online_server = "http://scores.example".to_string();
+        config.settings.music_volume = 0.3;
+        config.settings.window_size = 2;
+
+        let mut storage = MemoryStorage::default();
+        config.write(&mut storage).unwrap();
+        let (loaded, message) = Config::load(&mut storage, &MemoryStorage::default());
+        assert_eq!(message, None);
+        assert_eq!(loaded.bindings, config.bindings);
+        assert_eq!(loaded.online_server, config.online_server);
+        assert_eq!(loaded.settings, config.settings);
+    }
+
+    #[test]
+    fn unusable_config_falls_back_and_is_backed_up() {
+        let mut storage = MemoryStorage::default();
+        let broken = "[settings]\nmaster_volume = loud\n".to_string();
+        storage
+            .files
+            .insert(CONFIG_FILE.to_string(), broken.clone());
+
+        let (config, message) = Config::load(&mut storage, &MemoryStorage::default());
+        assert!(message.unwrap().contains("master_volume"));
+        assert_eq!(config.settings, Settings::default());
+        config.write(&mut storage).unwrap();
+        assert_eq!(storage.files[&backup_name(CONFIG_FILE)], broken);
+    }
+
+    #[test]
+    fn legacy_bindings_are_imported() {
+        let mut legacy = MemoryStorage::default();
+        legacy.files.insert(
+            LEGACY_BINDINGS_FILE.to_string(),
+            "[player1]\nfire = Z\n".to_string(),
+        );
+        let mut storage = MemoryStorage::default();
+
+        let (config, message) = Config::load(&mut storage, &legacy);
+        assert_eq!(message, None);
+        assert_eq!(
+            config.bindings.key(Scope::Player(0), Action::Fire),
+            KeyCode::Z
+        );
+        assert!(storage.files.contains_key(CONFIG_FILE));
+    }
+}
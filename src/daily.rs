@@ -0,0 +1,43 @@
+//! The daily challenge: a run whose seed comes from the UTC date, so
+//! everyone plays the same spawns that day. Only the first attempt of a day
+//! is ranked; the day it was used on is kept in the save.
+
+use crate::leaderboard::{DAILY_BOARD, utc_date};
+use crate::rng::Rng;
+use crate::save::SaveData;
+
+/// Stat holding the day of the last ranked attempt.
+const LAST_RANKED_DAY: &str = "daily.last_ranked_day";
+
+/// Days since the Unix epoch, in UTC.
+pub fn day(unix_seconds: f64) -> u64 {
+    (unix_seconds / 86_400.0).floor() as u64
+}
+
+/// `YYYY-MM-DD` of `day`.
+pub fn date(day: u64) -> String {
+    utc_date(day as f64 * 86_400.0)
+}
+
+/// Run seed for `day`, the same for every player.
+pub fn seed(day: u64) -> u64 {
+    // Scrambled so neighbouring days don't start from neighbouring states
+    Rng::new(day ^ 0xda11_c4a1).next_u64()
+}
+
+/// Board of `day`'s runs, both on this machine and online.
+pub fn board(day: u64) -> String {
+    format!("{DAILY_BOARD}-{}", date(day))
+}
+
+/// Whether the ranked attempt for `day` is still to be played.
+pub fn attempt_left(save: &SaveData, day: u64) -> bool {
+    save.stats
+        .get(LAST_RANKED_DAY)
+        .is_none_or(|last| *last < day)
+}
+
+/// Uses up the ranked attempt for `day`.
+pub fn use_attempt(save: &mut SaveData, day: u64) {
+    save.stats.insert(LAST_RANKED_DAY.to_string(), day);
+}
@@ -0,0 +1,71 @@
+//! Where the save and config files live. By default that's the platform's
+//! per-user directories, so the game finds them whichever directory it is
+//! started from. `--data-dir <dir>` keeps everything in one directory
+//! instead, for tests and portable installs.
+
+use std::env;
+use std::path::PathBuf;
+
+/// Subdirectory of the per-user directories that belongs to the game.
+const APP_DIR: &str = "my_game";
+
+pub struct Dirs {
+    /// Save data: scores, unlocks and stats.
+    pub data: PathBuf,
+    /// Settings the player may want to edit or copy between machines.
+    pub config: PathBuf,
+}
+
+impl Dirs {
+    /// Reads `--data-dir <dir>` or `--data-dir=<dir>`, falling back to the
+    /// per-user directories.
+    pub fn from_args(mut args: impl Iterator<Item = String>) -> Dirs {
+        while let Some(arg) = args.next() {
+            let dir = match arg.strip_prefix("--data-dir") {
+                Some("") => args.next(),
+                Some(value) => value.strip_prefix('=').map(str::to_string),
+                None => None,
+            };
+            if let Some(dir) = dir {
+                return Dirs {
+                    data: PathBuf::from(&dir),
+                    config: PathBuf::from(dir),
+                };
+            }
+        }
+        Dirs::per_user()
+    }
+
+    /// `$XDG_DATA_HOME` and `$XDG_CONFIG_HOME` on Linux and other Unixes,
+    /// Application Support on macOS and the roaming AppData on Windows.
+    /// Without a home directory everything goes in the current directory,
+    /// like it used to.
+    fn per_user() -> Dirs {
+        let home = env::var_os("HOME").map(PathBuf::from);
+        let xdg = |variable: &str, fallback: &str| {
+            env::var_os(variable)
+                .map(PathBuf::from)
+                .filter(|dir| dir.is_absolute())
+                .or_else(|| home.as_ref().map(|home| home.join(fallback)))
+        };
+        let (data, config) = if cfg!(target_os = "windows") {
+            let app_data = env::var_os("APPDATA").map(PathBuf::from);
+            (app_data.clone(), app_data)
+        } else if cfg!(target_os = "macos") {
+            let support = home
+                .as_ref()
+                .map(|home| home.join("Library/Application Support"));
+            (support.clone(), support)
+        } else {
+            (
+                xdg("XDG_DATA_HOME", ".local/share"),
+                xdg("XDG_CONFIG_HOME", ".config"),
+            )
+        };
+        let app_dir = |dir: Option<PathBuf>| dir.map_or_else(PathBuf::new, |dir| dir.join(APP_DIR));
+        Dirs {
+            data: app_dir(data),
+            config: app_dir(config),
+        }
+    }
+}
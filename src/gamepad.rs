@@ -0,0 +1,456 @@
+//! Gamepads for gameplay and menus. Pads are read through a
+//! `GamepadBackend`: gilrs on desktop (evdev on Linux, so a virtual uinput
+//! device shows up like any other pad) and nothing on the web. Any other
+//! `GamepadBackend` can be handed to `Gamepads::with_backend` to drive the
+//! game without hardware.
+
+use macroquad::prelude::*;
+
+use crate::input::{Button, PlayerInput};
+use crate::player::MAX_PLAYERS;
+
+/// Stick deflection ignored around the centre.
+pub const DEADZONE: f32 = 0.2;
+/// Stick deflection that counts as a d-pad press in menus.
+const MENU_THRESHOLD: f32 = 0.5;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PadButton {
+    South = 1,
+    East = 2,
+    West = 4,
+    North = 8,
+    Start = 16,
+    Select = 32,
+    DPadUp = 64,
+    DPadDown = 128,
+    DPadLeft = 256,
+    DPadRight = 512,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PadAxis {
+    LeftStickX,
+    LeftStickY,
+}
+
+/// Something that happened on the pad in slot `.0`. Stick axes range over
+/// -1.0..=1.0 with positive y down.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PadEvent {
+    Connected(usize),
+    Disconnected(usize),
+    Button(usize, PadButton, bool),
+    Axis(usize, PadAxis, f32),
+}
+
+pub trait GamepadBackend {
+    /// Appends everything that happened since the last poll.
+    fn poll(&mut self, events: &mut Vec<PadEvent>);
+}
+
+/// Backend for platforms without gamepad support.
+pub struct NoGamepads;
+
+impl GamepadBackend for NoGamepads {
+    fn poll(&mut self, _events: &mut Vec<PadEvent>) {}
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub struct GilrsBackend {
+    gilrs: gilrs::Gilrs,
+    /// The gilrs pad sitting in each slot.
+    slots: [Option<gilrs::GamepadId>; MAX_PLAYERS],
+    /// Pads that were plugged in before the game started.
+    startup: Vec<PadEvent>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl GilrsBackend {
+    pub fn new() -> Result<GilrsBackend, gilrs::Error> {
+        let gilrs = gilrs::Gilrs::new()?;
+        let mut backend = GilrsBackend {
+            gilrs,
+            slots: [None; MAX_PLAYERS],
+            startup: vec![],
+        };
+        // gilrs only reports pads connected later, so announce these ourselves
+        let ids: Vec<gilrs::GamepadId> = backend.gilrs.gamepads().map(|(id, _)| id).collect();
+        for id in ids {
+            if let Some(slot) = backend.connect(id) {
+                backend.startup.push(PadEvent::Connected(slot));
+            }
+        }
+        Ok(backend)
+    }
+
+    /// Puts `id` in the first free slot, unless it already has one.
+    fn connect(&mut self, id: gilrs::GamepadId) -> Option<usize> {
+        if self.slots.contains(&Some(id)) {
+            return None;
+        }
+        let slot = self.slots.iter().position(Option::is_none)?;
+        self.slots[slot] = Some(id);
+        Some(slot)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl GamepadBackend for GilrsBackend {
+    fn poll(&mut self, events: &mut Vec<PadEvent>) {
+        use gilrs::{Axis, EventType};
+
+        events.append(&mut self.startup);
+        while let Some(gilrs::Event { id, event, .. }) = self.gilrs.next_event() {
+            if let EventType::Connected = event {
+                if let Some(slot) = self.connect(id) {
+                    events.push(PadEvent::Connected(slot));
+                }
+                continue;
+            }
+            // Pads beyond the player count are ignored
+            let Some(slot) = self.slots.iter().position(|pad| *pad == Some(id)) else {
+                continue;
+            };
+            match event {
+                EventType::Disconnected => {
+                    self.slots[slot] = None;
+                    events.push(PadEvent::Disconnected(slot));
+                }
+                EventType::ButtonPressed(button, _) | EventType::ButtonReleased(button, _) => {
+                    if let Some(button) = pad_button(button) {
+                        let held = matches!(event, EventType::ButtonPressed(..));
+                        events.push(PadEvent::Button(slot, button, held));
+                    }
+                }
+                EventType::AxisChanged(Axis::LeftStickX, value, _) => {
+                    events.push(PadEvent::Axis(slot, PadAxis::LeftStickX, value));
+                }
+                // gilrs has y pointing up
+                EventType::AxisChanged(Axis::LeftStickY, value, _) => {
+                    events.push(PadEvent::Axis(slot, PadAxis::LeftStickY, -value));
+                }
+                // Some pads report the d-pad as a pair of axes
+                EventType::AxisChanged(Axis::DPadX, value, _) => {
+                    events.push(PadEvent::Button(slot, PadButton::DPadLeft, value < -0.5));
+                    events.push(PadEvent::Button(slot, PadButton::DPadRight, value > 0.5));
+                }
+                EventType::AxisChanged(Axis::DPadY, value, _) => {
+                    events.push(PadEvent::Button(slot, PadButton::DPadUp, value > 0.5));
+                    events.push(PadEvent::Button(slot, PadButton::DPadDown, value < -0.5));
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn pad_button(button: gilrs::Button) -> Option<PadButton> {
+    use gilrs::Button;
+
+    Some(match button {
+        Button::South => PadButton::South,
+        Button::East => PadButton::East,
+        Button::West => PadButton::West,
+        Button::North => PadButton::North,
+        Button::Start => PadButton::Start,
+        Button::Select => PadButton::Select,
+        Button::DPadUp => PadButton::DPadUp,
+        Button::DPadDown => PadButton::DPadDown,
+        Button::DPadLeft => PadButton::DPadLeft,
+        Button::DPadRight => PadButton::DPadRight,
+        _ => return None,
+    })
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct PadState {
+    connected: bool,
+    buttons: u16,
+    stick: Vec2,
+}
+
+impl PadState {
+    fn held(&self, button: PadButton) -> bool {
+        self.buttons & button as u16 != 0
+    }
+
+    /// D-pad and stick folded into one direction for menus.
+    fn menu_direction(&self) -> IVec2 {
+        let axis = |negative, positive, stick: f32| {
+            if self.held(negative) || stick < -MENU_THRESHOLD {
+                -1
+            } else if self.held(positive) || stick > MENU_THRESHOLD {
+                1
+            } else {
+                0
+            }
+        };
+        ivec2(
+            axis(PadButton::DPadLeft, PadButton::DPadRight, self.stick.x),
+            axis(PadButton::DPadUp, PadButton::DPadDown, self.stick.y),
+        )
+    }
+}
+
+/// Every pad slot, updated once per frame.
+pub struct Gamepads {
+    backend: Box<dyn GamepadBackend>,
+    pads: [PadState; MAX_PLAYERS],
+    previous: [PadState; MAX_PLAYERS],
+    events: Vec<PadEvent>,
+}
+
+impl Gamepads {
+    /// Uses gilrs where available, and carries on without pads if it fails.
+    pub fn new() -> Gamepads {
+        #[cfg(not(target_arch = "wasm32"))]
+        match GilrsBackend::new() {
+            Ok(backend) => return Gamepads::with_backend(Box::new(backend)),
+            Err(err) => error!("Gamepads unavailable: {}", err),
+        }
+        Gamepads::with_backend(Box::new(NoGamepads))
+    }
+
+    pub fn with_backend(backend: Box<dyn GamepadBackend>) -> Gamepads {
+        Gamepads {
+            backend,
+            pads: [PadState::default(); MAX_PLAYERS],
+            previous: [PadState::default(); MAX_PLAYERS],
+            events: vec![],
+        }
+    }
+
+    pub fn update(&mut self) {
+        self.previous = self.pads;
+        self.events.clear();
+        self.backend.poll(&mut self.events);
+        for event in &self.events {
+            match *event {
+                PadEvent::Connected(slot) => {
+                    self.pads[slot] = PadState {
+                        connected: true,
+                        ..Default::default()
+                    };
+                }
+                PadEvent::Disconnected(slot) => self.pads[slot] = PadState::default(),
+                PadEvent::Button(slot, button, held) => {
+                    if held {
+                        self.pads[slot].buttons |= button as u16;
+                    } else {
+                        self.pads[slot].buttons &= !(button as u16);
+                    }
+                }
+                PadEvent::Axis(slot, PadAxis::LeftStickX, value) => self.pads[slot].stick.x = value,
+                PadEvent::Axis(slot, PadAxis::LeftStickY, value) => self.pads[slot].stick.y = value,
+            }
+        }
+    }
+
+    /// Everything that happened during the last update, e.g. for reacting
+    /// to pads being unplugged.
+    pub fn events(&self) -> &[PadEvent] {
+        &self.events
+    }
+
+    pub fn connected(&self, slot: usize) -> bool {
+        self.pads[slot].connected
+    }
+
+    /// Pressed since the last update.
+    pub fn pressed(&self, slot: usize, button: PadButton) -> bool {
+        self.pads[slot].held(button) && !self.previous[slot].held(button)
+    }
+
+    pub fn any_pressed(&self, button: PadButton) -> bool {
+        (0..MAX_PLAYERS).any(|slot| self.pressed(slot, button))
+    }
+
+    /// Menu step from any pad: the d-pad or stick moving off centre.
+    pub fn menu_direction(&self) -> IVec2 {
+        let mut direction = IVec2::ZERO;
+        for (pad, previous) in self.pads.iter().zip(&self.previous) {
+            let (now, before) = (pad.menu_direction(), previous.menu_direction());
+            if now.x != before.x {
+                direction.x += now.x;
+            }
+            if now.y != before.y {
+                direction.y += now.y;
+            }
+        }
+        direction
+    }
+
+    /// Ship controls for `slot`: stick or d-pad to move, South fires, East
+    /// bombs and West dashes.
+    pub fn input(&self, slot: usize) -> PlayerInput {
+        let pad = &self.pads[slot];
+        let mut input = PlayerInput::default();
+        input.set(Button::Left, pad.held(PadButton::DPadLeft));
+        input.set(Button::Right, pad.held(PadButton::DPadRight));
+        input.set(Button::Up, pad.held(PadButton::DPadUp));
+        input.set(Button::Down, pad.held(PadButton::DPadDown));
+        input.set(Button::Fire, pad.held(PadButton::South));
+        input.set(Button::Bomb, pad.held(PadButton::East));
+        input.set(Button::Dash, pad.held(PadButton::West));
+
+        // Rescale past the deadzone so small deflections still move slowly
+        let length = pad.stick.length();
+        if length > DEADZONE {
+            let scaled = ((length - DEADZONE) / (1.0 - DEADZONE)).min(1.0);
+            input.set_stick(pad.stick / length * scaled);
+        }
+        input
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+
+    /// Hands over whatever the test queued since the last poll.
+    struct FakePads(Rc<RefCell<Vec<PadEvent>>>);
+
+    impl GamepadBackend for FakePads {
+        fn poll(&mut self, events: &mut Vec<PadEvent>) {
+            events.append(&mut self.0.borrow_mut());
+        }
+    }
+
+    fn fake() -> (Gamepads, Rc<RefCell<Vec<PadEvent>>>) {
+        let queue = Rc::new(RefCell::new(vec![]));
+        let gamepads = Gamepads::with_backend(Box::new(FakePads(queue.clone())));
+        (gamepads, queue)
+    }
+
+    fn stick(gamepads: &mut Gamepads, queue: &RefCell<Vec<PadEvent>>, x: f32, y: f32) -> Vec2 {
+        queue.borrow_mut().extend([
+            PadEvent::Axis(0, PadAxis::LeftStickX, x),
+            PadEvent::Axis(0, PadAxis::LeftStickY, y),
+        ]);
+        gamepads.update();
+        gamepads.input(0).movement()
+    }
+
+    #[test]
+    fn stick_is_rescaled_past_the_deadzone() {
+        let (mut gamepads, queue) = fake();
+        queue.borrow_mut().push(PadEvent::Connected(0));
+        gamepads.update();
+
+        let close = |a: Vec2, b: Vec2| a.abs_diff_eq(b, 1.0 / 127.0);
+        assert_eq!(stick(&mut gamepads, &queue, 0.1, -0.1), Vec2::ZERO);
+        assert_eq!(stick(&mut gamepads, &queue, DEADZONE, 0.0), Vec2::ZERO);
+        let halfway = DEADZONE + (1.0 - DEADZONE) / 2.0;
+        assert!(close(
+            stick(&mut gamepads, &queue, halfway, 0.0),
+            vec2(0.5, 0.0)
+        ));
+        assert!(close(
+            stick(&mut gamepads, &queue, 0.0, -1.0),
+            vec2(0.0, -1.0)
+        ));
+        // Direction is kept, only the length is rescaled
+        let diagonal = stick(&mut gamepads, &queue, 0.6, 0.8);
+        assert!(close(diagonal, vec2(0.6, 0.8)));
+        // Corners of square sticks don't go past full deflection
+        let corner = stick(&mut gamepads, &queue, 1.0, 1.0);
+        assert!(corner.length() <= 1.0 + 1.0 / 127.0);
+    }
+
+    #[test]
+    fn pads_come_and_go() {
+        let (mut gamepads, queue) = fake();
+        assert!(!gamepads.connected(1));
+
+        queue.borrow_mut().extend([
+            PadEvent::Connected(1),
+            PadEvent::Button(1, PadButton::South, true),
+        ]);
+        gamepads.update();
+        assert!(gamepads.connected(1));
+        assert!(!gamepads.connected(0));
+        assert_eq!(
+            gamepads.events(),
+            [
+                PadEvent::Connected(1),
+                PadEvent::Button(1, PadButton::South, true)
+            ]
+        );
+        assert!(gamepads.input(1).held(Button::Fire));
+
+        queue.borrow_mut().push(PadEvent::Disconnected(1));
+        gamepads.update();
+        assert!(!gamepads.connected(1));
+        assert_eq!(gamepads.events(), [PadEvent::Disconnected(1)]);
+        // A pad unplugged mid-press doesn't leave the button held
+        assert_eq!(gamepads.input(1), PlayerInput::default());
+
+        // Plugged back in, it starts from a clean state
+        queue.borrow_mut().push(PadEvent::Connected(1));
+        gamepads.update();
+        assert!(gamepads.connected(1));
+        assert!(!gamepads.input(1).held(Button::Fire));
+        gamepads.update();
+        assert!(gamepads.events().is_empty());
+    }
+
+    #[test]
+    fn menu_direction_only_steps_on_changes() {
+        let (mut gamepads, queue) = fake();
+        queue.borrow_mut().extend([
+            PadEvent::Connected(0),
+            PadEvent::Button(0, PadButton::DPadDown, true),
+        ]);
+        gamepads.update();
+        assert_eq!(gamepads.menu_direction(), ivec2(0, 1));
+        // Holding it doesn't keep stepping
+        gamepads.update();
+        assert_eq!(gamepads.menu_direction(), IVec2::ZERO);
+        // Neither does letting go
+        queue
+            .borrow_mut()
+            .push(PadEvent::Button(0, PadButton::DPadDown, false));
+        gamepads.update();
+        assert_eq!(gamepads.menu_direction(), IVec2::ZERO);
+
+        // The stick steps once it crosses the threshold
+        queue
+            .borrow_mut()
+            .push(PadEvent::Axis(0, PadAxis::LeftStickX, MENU_THRESHOLD - 0.1));
+        gamepads.update();
+        assert_eq!(gamepads.menu_direction(), IVec2::ZERO);
+        queue
+            .borrow_mut()
+            .push(PadEvent::Axis(0, PadAxis::LeftStickX, -0.9));
+        gamepads.update();
+        assert_eq!(gamepads.menu_direction(), ivec2(-1, 0));
+        queue
+            .borrow_mut()
+            .push(PadEvent::Axis(0, PadAxis::LeftStickX, -1.0));
+        gamepads.update();
+        assert_eq!(gamepads.menu_direction(), IVec2::ZERO);
+
+        // Flicking straight across steps the other way at once
+        queue
+            .borrow_mut()
+            .push(PadEvent::Axis(0, PadAxis::LeftStickX, 1.0));
+        gamepads.update();
+        assert_eq!(gamepads.menu_direction(), ivec2(1, 0));
+
+        // The same button on another pad steps again
+        queue.borrow_mut().extend([
+            PadEvent::Connected(1),
+            PadEvent::Button(1, PadButton::DPadUp, true),
+        ]);
+        gamepads.update();
+        assert_eq!(gamepads.menu_direction(), ivec2(0, -1));
+        assert!(gamepads.pressed(1, PadButton::DPadUp));
+        gamepads.update();
+        assert!(!gamepads.pressed(1, PadButton::DPadUp));
+    }
+}
@@ -0,0 +1,52 @@
+//! Minimal reader for the `[section]` / `key = value` data files in
+//! `assets/` and the body of the save file.
+
+use std::str::FromStr;
+
+pub struct Section {
+    pub name: String,
+    pub entries: Vec<(String, String)>,
+}
+
+impl Section {
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn parse<T: FromStr>(&self, key: &str) -> Option<T> {
+        self.get(key).and_then(|v| v.parse().ok())
+    }
+}
+
+/// Splits `text` into sections. Blank lines and lines starting with `#` are
+/// ignored, as are lines that are neither a header nor a `key = value` pair.
+/// Entries before the first header land in a section with an empty name.
+pub fn parse(text: &str) -> Vec<Section> {
+    let mut sections = vec![Section {
+        name: String::new(),
+        entries: vec![],
+    }];
+
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            sections.push(Section {
+                name: name.trim().to_string(),
+                entries: vec![],
+            });
+        } else if let Some((key, value)) = line.split_once('=') {
+            let section = sections.last_mut().unwrap();
+            section
+                .entries
+                .push((key.trim().to_string(), value.trim().to_string()));
+        }
+    }
+
+    sections.retain(|section| !section.name.is_empty() || !section.entries.is_empty());
+    sections
+}
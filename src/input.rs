@@ -0,0 +1,99 @@
+use macroquad::prelude::*;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Button {
+    Left = 1,
+    Right = 2,
+    Up = 4,
+    Down = 8,
+    Fire = 16,
+    Bomb = 32,
+    Dash = 64,
+}
+
+/// Buttons one player holds during a tick, plus an analog stick quantized
+/// to bytes so every peer simulates exactly the same movement. This is all
+/// the simulation sees of the outside world, and what the netcode sends over
+/// the wire.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PlayerInput {
+    buttons: u8,
+    stick: [i8; 2],
+}
+
+impl PlayerInput {
+    /// Bytes used by `to_bytes`.
+    pub const SIZE: usize = 3;
+
+    /// Input held on either `self` or `other`, e.g. keyboard and gamepad.
+    /// The stick with the larger deflection wins.
+    pub fn merge(self, other: PlayerInput) -> PlayerInput {
+        let deflection = |input: PlayerInput| {
+            i32::from(input.stick[0]).pow(2) + i32::from(input.stick[1]).pow(2)
+        };
+        PlayerInput {
+            buttons: self.buttons | other.buttons,
+            stick: if deflection(other) > deflection(self) {
+                other.stick
+            } else {
+                self.stick
+            },
+        }
+    }
+
+    /// Sets the analog stick, each axis in -1.0..=1.0 with positive y down.
+    pub fn set_stick(&mut self, stick: Vec2) {
+        let stick = stick.clamp(Vec2::splat(-1.0), Vec2::ONE) * 127.0;
+        self.stick = [stick.x.round() as i8, stick.y.round() as i8];
+    }
+
+    /// Points the stick at `offset`, at full deflection when it is more
+    /// than `reach` away, so the ship slows down as it closes in.
+    pub fn steer(&mut self, offset: Vec2, reach: f32) {
+        self.set_stick((offset / reach).clamp_length_max(1.0));
+    }
+
+    /// Direction to move in, each axis in -1.0..=1.0 with positive y down.
+    /// Direction buttons override the stick.
+    pub fn movement(self) -> Vec2 {
+        let axis = |negative, positive, stick: i8| match (self.held(negative), self.held(positive))
+        {
+            (true, false) => -1.0,
+            (false, true) => 1.0,
+            (true, true) => 0.0,
+            (false, false) => f32::from(stick) / 127.0,
+        };
+        vec2(
+            axis(Button::Left, Button::Right, self.stick[0]),
+            axis(Button::Up, Button::Down, self.stick[1]),
+        )
+    }
+
+    pub fn set(&mut self, button: Button, held: bool) {
+        if held {
+            self.buttons |= button as u8;
+        } else {
+            self.buttons &= !(button as u8);
+        }
+    }
+
+    pub fn held(self, button: Button) -> bool {
+        self.buttons & button as u8 != 0
+    }
+
+    /// Held now but not in `previous`.
+    pub fn pressed(self, previous: PlayerInput, button: Button) -> bool {
+        self.held(button) && !previous.held(button)
+    }
+
+    pub fn to_bytes(self) -> [u8; PlayerInput::SIZE] {
+        [self.buttons, self.stick[0] as u8, self.stick[1] as u8]
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> PlayerInput {
+        PlayerInput {
+            buttons: bytes[0],
+            stick: [bytes[1] as i8, bytes[2] as i8],
+        }
+    }
+}
@@ -0,0 +1,276 @@
+//! The best runs on this machine, stored in the save file with one
+//! `[entry]` section per run, best first. Each mode keeps a board of its
+//! own, named by the entry's `board` key.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use macroquad::prelude::*;
+
+use crate::ini::Section;
+
+pub const MAX_ENTRIES: usize = 10;
+pub const MAX_NAME_LENGTH: usize = 10;
+/// Board of the endless mode started from Play, and of entries saved before
+/// there were boards.
+pub const ENDLESS_BOARD: &str = "endless";
+/// Daily challenge runs, with a board per day from `daily::board` named by
+/// this and the date. On its own it stands for today's board.
+pub const DAILY_BOARD: &str = "daily";
+/// Time attack runs, one board per time limit.
+pub const TIME_ATTACK_2_BOARD: &str = "time-attack-2";
+pub const TIME_ATTACK_5_BOARD: &str = "time-attack-5";
+pub const PACIFIST_BOARD: &str = "pacifist";
+/// Every board, in the order they are listed under Records.
+pub const BOARDS: [&str; 5] = [
+    ENDLESS_BOARD,
+    DAILY_BOARD,
+    TIME_ATTACK_2_BOARD,
+    TIME_ATTACK_5_BOARD,
+    PACIFIST_BOARD,
+];
+
+/// Name of `board` in menus.
+pub fn name(board: &str) -> &'static str {
+    match board {
+        board if board.starts_with(DAILY_BOARD) => "Daily",
+        TIME_ATTACK_2_BOARD => "Time Attack 2:00",
+        TIME_ATTACK_5_BOARD => "Time Attack 5:00",
+        PACIFIST_BOARD => "Pacifist",
+        _ => "Endless",
+    }
+}
+
+/// Heading shown above `board`.
+pub fn title(board: &str) -> &'static str {
+    match board {
+        board if board.starts_with(DAILY_BOARD) => "DAILY CHALLENGE",
+        TIME_ATTACK_2_BOARD => "TIME ATTACK 2:00",
+        TIME_ATTACK_5_BOARD => "TIME ATTACK 5:00",
+        PACIFIST_BOARD => "PACIFIST",
+        _ => "HIGH SCORES",
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    pub name: String,
+    pub score: u32,
+    /// UTC day the run ended on, as `YYYY-MM-DD`.
+    pub date: String,
+    /// Seconds spent playing, not counting the shop.
+    pub duration: f32,
+    pub seed: u64,
+    /// Slot of the ship flown, which decides its colour.
+    pub ship: usize,
+}
+
+impl Entry {
+    /// Reads an `[entry]` section, or `None` if it has no score.
+    pub fn from_section(section: &Section) -> Option<Entry> {
+        Some(Entry {
+            name: section.get("name").unwrap_or_default().to_string(),
+            score: section.parse("score")?,
+            date: section.get("date").unwrap_or_default().to_string(),
+            duration: section.parse("duration").unwrap_or_default(),
+            seed: section.parse("seed").unwrap_or_default(),
+            ship: section.parse("ship").unwrap_or_default(),
+        })
+    }
+
+    /// The `key = value` lines of the entry, without a section header.
+    pub fn to_ini(&self) -> String {
+        format!(
+            "name = {}\nscore = {}\ndate = {}\nduration = {}\nseed = {}\nship = {}\n",
+            self.name, self.score, self.date, self.duration, self.seed, self.ship
+        )
+    }
+}
+
+/// Every `[entry]` section in `sections`, best first.
+pub fn parse_entries(sections: &[Section]) -> Vec<Entry> {
+    let mut entries: Vec<Entry> = sections
+        .iter()
+        .filter(|section| section.name == "entry")
+        .filter_map(Entry::from_section)
+        .collect();
+    entries.sort_by_key(|entry| std::cmp::Reverse(entry.score));
+    entries
+}
+
+/// The `[entry]` sections in `sections`, sorted into boards.
+pub fn parse_boards(sections: &[Section]) -> BTreeMap<String, Leaderboard> {
+    let mut boards: BTreeMap<String, Leaderboard> = BTreeMap::new();
+    for section in sections.iter().filter(|section| section.name == "entry") {
+        if let Some(entry) = Entry::from_section(section) {
+            let board = section.get("board").unwrap_or(ENDLESS_BOARD);
+            boards
+                .entry(board.to_string())
+                .or_default()
+                .entries
+                .push(entry);
+        }
+    }
+    for leaderboard in boards.values_mut() {
+        leaderboard
+            .entries
+            .sort_by_key(|entry| std::cmp::Reverse(entry.score));
+        leaderboard.entries.truncate(MAX_ENTRIES);
+    }
+    boards
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Leaderboard {
+    pub entries: Vec<Entry>,
+}
+
+impl Leaderboard {
+    /// Reads the `[entry]` sections out of `sections`. Entries missing a
+    /// score are skipped.
+    pub fn from_sections(sections: &[Section]) -> Leaderboard {
+        let mut entries = parse_entries(sections);
+        entries.truncate(MAX_ENTRIES);
+        Leaderboard { entries }
+    }
+
+    /// The entries as `[entry]` sections on `board`.
+    pub fn to_ini(&self, board: &str) -> String {
+        let mut text = String::new();
+        for entry in &self.entries {
+            writeln!(text, "[entry]\nboard = {board}\n{}", entry.to_ini()).unwrap();
+        }
+        text
+    }
+
+    /// Whether `score` would make it onto the board.
+    pub fn qualifies(&self, score: u32) -> bool {
+        score > 0
+            && (self.entries.len() < MAX_ENTRIES
+                || self.entries.last().is_some_and(|last| score > last.score))
+    }
+
+    /// Adds `entry` below any equal scores. Returns its rank, or `None` if
+    /// it didn't make the cut.
+    pub fn insert(&mut self, entry: Entry) -> Option<usize> {
+        if !self.qualifies(entry.score) {
+            return None;
+        }
+        let rank = self
+            .entries
+            .iter()
+            .position(|other| other.score < entry.score)
+            .unwrap_or(self.entries.len());
+        self.entries.insert(rank, entry);
+        self.entries.truncate(MAX_ENTRIES);
+        Some(rank)
+    }
+}
+
+/// Keeps the characters a name may hold: letters, digits and spaces,
+/// upper-cased.
+pub fn name_char(character: char) -> Option<char> {
+    (character.is_ascii_alphanumeric() || character == ' ').then(|| character.to_ascii_uppercase())
+}
+
+/// `m:ss` for a run duration.
+pub fn format_duration(seconds: f32) -> String {
+    let seconds = seconds as u32;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+/// `YYYY-MM-DD` for a Unix timestamp, in UTC.
+pub fn utc_date(unix_seconds: f64) -> String {
+    // Days to civil date, from Howard Hinnant's date algorithms
+    let days = (unix_seconds / 86_400.0).floor() as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+/// Draws `board` centred on screen, highlighting the entry at `highlight`.
+pub fn draw(board: &str, leaderboard: &Leaderboard, font: &Font, highlight: Option<usize>) {
+    draw_entries(title(board), &leaderboard.entries, 0, font, highlight);
+}
+
+/// Draws up to `MAX_ENTRIES` of `entries` from rank `first` on, like `draw`.
+/// `highlight` is a rank.
+pub fn draw_entries(
+    title: &str,
+    entries: &[Entry],
+    first: usize,
+    font: &Font,
+    highlight: Option<usize>,
+) {
+    let columns: [(&str, f32); 7] = [
+        ("", 0.0),
+        ("NAME", 40.0),
+        ("SCORE", 220.0),
+        ("SHIP", 340.0),
+        ("TIME", 400.0),
+        ("DATE", 480.0),
+        ("SEED", 640.0),
+    ];
+    let left = (screen_width() - 780.0).max(0.0) / 2.0;
+    let top = screen_height() / 2.0 - 190.0;
+    let text = |text: &str, x: f32, y: f32, font_size: u16, color: Color| {
+        draw_text_ex(
+            text,
+            x,
+            y,
+            TextParams {
+                font: Some(font),
+                font_size,
+                color,
+                ..Default::default()
+            },
+        );
+    };
+
+    let title_dimensions = measure_text(title, Some(font), 40, 1.0);
+    text(
+        title,
+        screen_width() / 2.0 - title_dimensions.width / 2.0,
+        top - 40.0,
+        40,
+        WHITE,
+    );
+    for (name, x) in columns {
+        text(name, left + x, top, 14, GRAY);
+    }
+    if entries.is_empty() {
+        text("NO RUNS YET", left, top + 40.0, 20, WHITE);
+    }
+    let shown = entries.iter().enumerate().skip(first).take(MAX_ENTRIES);
+    for (row, (rank, entry)) in shown.enumerate() {
+        let y = top + 40.0 + row as f32 * 32.0;
+        let color = if highlight == Some(rank) {
+            YELLOW
+        } else {
+            WHITE
+        };
+        let cells = [
+            format!("{:>2}", rank + 1),
+            entry.name.clone(),
+            entry.score.to_string(),
+            format!("P{}", entry.ship + 1),
+            format_duration(entry.duration),
+            entry.date.clone(),
+            entry.seed.to_string(),
+        ];
+        for (cell, (_, x)) in cells.iter().zip(columns) {
+            text(cell, left + x, y, 14, color);
+        }
+    }
+}
@@ -1,13 +1,86 @@
-use macroquad::audio::{
-    PlaySoundParams, Sound, load_sound, play_sound, play_sound_once, set_sound_volume,
-};
-use macroquad::experimental::animation::{AnimatedSprite, Animation};
+use achievements::Achievements;
+use bindings::{Action, Bindings, Scope};
+use campaign::{Level, Rating};
+use config::Config;
+use gamepad::{Gamepads, PadButton, PadEvent};
+use input::PlayerInput;
+use leaderboard::{DAILY_BOARD, ENDLESS_BOARD, Entry, MAX_NAME_LENGTH, PACIFIST_BOARD};
+use macroquad::audio::{PlaySoundParams, Sound, load_sound, play_sound, set_sound_volume};
 use macroquad::experimental::collections::storage;
 use macroquad::experimental::coroutines::start_coroutine;
 use macroquad::prelude::*;
-use macroquad::ui::{Skin, hash, root_ui};
-use macroquad_particles::{self as particles, AtlasConfig, ColorCurve, Emitter, EmitterConfig};
-use std::fs;
+use macroquad::ui::{Id, Skin, hash, root_ui};
+use macroquad_particles::{self as particles, AtlasConfig, ColorCurve};
+use netcode::{ARENA_SIZE, NetGame, NetOptions};
+use online::{OnlineLeaderboard, Scores, Submission};
+use persist::{Storage, Storages};
+use player::MAX_PLAYERS;
+use playfield::{BIG_ENEMY_SIZE, Event, FIRE_COOLDOWN, MOVEMENT_SPEED, Playfield, TICK};
+use replay::Replay;
+use save::SaveData;
+use settings::{FrameLimiter, Setting};
+use shop::{Purchases, ShopItem, Upgrade};
+use stats::RunTracker;
+use touch::TouchControls;
+use tutorial::Tutorial;
+use versus::Versus;
+use view::PlayfieldView;
+
+mod achievements;
+mod bindings;
+mod campaign;
+mod config;
+mod daily;
+#[cfg(not(target_arch = "wasm32"))]
+mod dirs;
+mod gamepad;
+mod ini;
+mod input;
+mod leaderboard;
+mod mouse;
+mod netcode;
+mod online;
+mod persist;
+mod player;
+mod playfield;
+mod popups;
+mod replay;
+mod rng;
+mod save;
+mod settings;
+mod shop;
+mod stats;
+mod time_attack;
+mod touch;
+mod tutorial;
+mod versus;
+mod view;
+
+const MAIN_MENU: [&str; 7] = [
+    "Play", "Daily", "Versus", "Records", "Settings", "Controls", "Quit",
+];
+const PLAY_MENU: [&str; 8] = [
+    "Endless",
+    "Campaign",
+    "Time Attack 2:00",
+    "Time Attack 5:00",
+    "Pacifist",
+    "Practice",
+    "Tutorial",
+    "Back",
+];
+/// Game speeds to pick from in practice.
+const PRACTICE_SPEEDS: [f32; 4] = [0.25, 0.5, 1.0, 2.0];
+/// Choices over a paused run.
+const PAUSE_MENU: [&str; 4] = ["Resume", "Restart", "Settings", "Quit to Menu"];
+const TUTORIAL_OFFER: [&str; 2] = ["Play Tutorial", "Skip"];
+const RECORDS_MENU: [&str; 5] = [
+    "High Scores",
+    "Online Scores",
+    "Achievements",
+    "Lifetime Stats",
+    "Back",
+];
 
 #[cfg(not(target_arch = "wasm32"))]
 const FRAGMENT_SHADER: &str = include_str!("starfield-shader.glsl");
@@ -39,6 +112,10 @@ struct Resources {
     sound_explosion: Sound,
     sound_laser: Sound,
     ui_skin: Skin,
+    shop_skin: Skin,
+    shop_items: Vec<ShopItem>,
+    levels: Vec<Level>,
+    font: Font,
 }
 
 impl Resources {
@@ -64,6 +141,9 @@ impl Resources {
         let button_background = load_image("button_background.png").await?;
         let button_clicked_background = load_image("button_clicked_background.png").await?;
         let font = load_file("atari_games.ttf").await?;
+        let atari_font = load_ttf_font_from_bytes(&font)?;
+        let shop_items = shop::parse_inventory(&load_string("shop.ini").await?);
+        let levels = campaign::parse_levels(&load_string("levels.ini").await?);
 
         let window_style = root_ui()
             .style_builder()
@@ -74,8 +154,8 @@ impl Resources {
 
         let button_style = root_ui()
             .style_builder()
-            .background(button_background)
-            .background_clicked(button_clicked_background)
+            .background(button_background.clone())
+            .background_clicked(button_clicked_background.clone())
             .background_margin(RectOffset::new(16.0, 16.0, 16.0, 16.0))
             .margin(RectOffset::new(16.0, 0.0, -8.0, -8.0))
             .font(&font)
@@ -92,9 +172,28 @@ impl Resources {
             .font_size(28)
             .build();
 
+        let shop_button_style = root_ui()
+            .style_builder()
+            .background(button_background)
+            .background_clicked(button_clicked_background)
+            .background_margin(RectOffset::new(8.0, 8.0, 8.0, 8.0))
+            .margin(RectOffset::new(8.0, 0.0, -4.0, -4.0))
+            .font(&font)
+            .unwrap()
+            .text_color(WHITE)
+            .font_size(28)
+            .build();
+
         let ui_skin = Skin {
-            window_style,
+            window_style: window_style.clone(),
             button_style,
+            label_style: label_style.clone(),
+            ..root_ui().default_skin()
+        };
+
+        let shop_skin = Skin {
+            window_style,
+            button_style: shop_button_style,
             label_style,
             ..root_ui().default_skin()
         };
@@ -109,6 +208,10 @@ impl Resources {
             sound_explosion,
             sound_laser,
             ui_skin,
+            shop_skin,
+            shop_items,
+            levels,
+            font: atari_font,
         })
     }
 
@@ -141,18 +244,104 @@ impl Resources {
 
 enum GameState {
     MainMenu,
+    PlayMenu,
+    /// Asks first-time players whether they want the tutorial.
+    TutorialOffer,
+    LevelSelect,
+    LevelComplete,
+    Controls,
+    /// Audio, video and control options, applied as they are changed.
+    Settings,
     Playing,
     Paused,
+    /// Spawning and settings overlay of a practice run.
+    PracticeMenu,
+    Shop,
     GameOver,
+    /// Results of a time attack run.
+    TimeUp,
+    NameEntry,
+    Leaderboard,
+    OnlineScores,
+    Records,
+    /// Picks a board to show, local or online.
+    Boards,
+    Achievements,
+    LifetimeStats,
+    Online,
+    Versus,
 }
 
-#[derive(Debug)]
+/// Rules the run in `field` is played by.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum RunMode {
+    Endless,
+    /// `ranked` is unset when the day's ranked attempt was already used.
+    Daily {
+        day: u64,
+        ranked: bool,
+    },
+    /// Index into `Resources::levels`.
+    Campaign {
+        level: usize,
+    },
+    /// Index into `time_attack::LIMITS`.
+    TimeAttack {
+        limit: usize,
+    },
+    Pacifist,
+    /// Sandbox where nothing is recorded.
+    Practice,
+    Tutorial,
+}
+
+impl RunMode {
+    /// Local board the run's scores go on, if it is ranked.
+    fn board(self) -> Option<String> {
+        match self {
+            RunMode::Endless => Some(ENDLESS_BOARD.to_string()),
+            RunMode::Daily { day, ranked: true } => Some(daily::board(day)),
+            RunMode::TimeAttack { limit } => Some(time_attack::board(limit).to_string()),
+            RunMode::Pacifist => Some(PACIFIST_BOARD.to_string()),
+            RunMode::Daily { ranked: false, .. }
+            | RunMode::Campaign { .. }
+            | RunMode::Practice
+            | RunMode::Tutorial => None,
+        }
+    }
+
+    /// Whether the run's score counts for the high score. Time attack and
+    /// pacifist score by other rules and only go on their own boards.
+    fn sets_high_score(self) -> bool {
+        match self {
+            RunMode::Endless | RunMode::Daily { .. } | RunMode::Campaign { .. } => true,
+            RunMode::TimeAttack { .. }
+            | RunMode::Pacifist
+            | RunMode::Practice
+            | RunMode::Tutorial => false,
+        }
+    }
+
+    fn online_board(self) -> Option<String> {
+        match self {
+            RunMode::Endless => Some(ENDLESS_BOARD.to_string()),
+            RunMode::Daily { day, .. } => Some(daily::board(day)),
+            RunMode::TimeAttack { limit } => Some(time_attack::board(limit).to_string()),
+            RunMode::Pacifist => Some(PACIFIST_BOARD.to_string()),
+            RunMode::Campaign { .. } | RunMode::Practice | RunMode::Tutorial => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
 struct Shape {
     size: f32,
     speed: f32,
     x: f32,
     y: f32,
     collided: bool,
+    /// Damage it takes to destroy. Only enemies take damage.
+    health: u32,
 }
 
 impl Shape {
@@ -208,112 +397,179 @@ fn particle_exhaust() -> particles::EmitterConfig {
     }
 }
 
-#[macroquad::main("MyGame")]
-async fn main() -> Result<(), macroquad::Error> {
-    const MOVEMENT_SPEED: f32 = 200.0;
-
-    rand::srand(miniquad::date::now() as u64);
-
-    let mut squares = vec![];
-    let mut bullets = vec![];
-    let mut circle = Shape {
-        size: 32.0,
-        speed: MOVEMENT_SPEED,
-        x: screen_width() / 2.0,
-        y: screen_height() / 2.0,
-        collided: false,
-    };
-    let mut exhaust = (
-        Emitter::new(EmitterConfig {
-            amount: circle.size.round() as u32 * 2,
-            ..particle_exhaust()
-        }),
-        vec2(circle.x, circle.y + circle.size / 2.0),
-    );
-    let mut explosions: Vec<(Emitter, Vec2)> = vec![];
+/// Writes `save` to disk. Failures are logged and kept in `status` for the
+/// main menu to show.
+fn write_save(save: &SaveData, storage: &mut dyn Storage, status: &mut Option<String>) {
+    if let Err(err) = save.write(storage) {
+        let message = format!("Could not save: {err}");
+        error!("{}", message);
+        *status = Some(message);
+    }
+}
 
-    // Load textures
-    set_pc_assets_folder("assets");
+fn write_replay(replay: &Replay, storage: &mut dyn Storage, status: &mut Option<String>) {
+    if let Err(err) = replay.save(storage) {
+        let message = format!("Could not save the replay: {err}");
+        error!("{}", message);
+        *status = Some(message);
+    }
+}
 
-    let window_size = vec2(370.0, 320.0);
-
-    // Create animations
-    let mut bullet_sprite = AnimatedSprite::new(
-        16,
-        16,
-        &[
-            Animation {
-                name: "bullet".to_string(),
-                row: 0,
-                frames: 2,
-                fps: 12,
-            },
-            Animation {
-                name: "bolt".to_string(),
-                row: 1,
-                frames: 2,
-                fps: 12,
-            },
-        ],
-        true,
-    );
-    bullet_sprite.set_animation(1);
-
-    let mut ship_sprite = AnimatedSprite::new(
-        16,
-        24,
-        &[
-            Animation {
-                name: "idle".to_string(),
-                row: 0,
-                frames: 2,
-                fps: 12,
-            },
-            Animation {
-                name: "left".to_string(),
-                row: 2,
-                frames: 2,
-                fps: 12,
-            },
-            Animation {
-                name: "right".to_string(),
-                row: 4,
-                frames: 2,
-                fps: 12,
-            },
-        ],
-        true,
+/// The run, frozen and dimmed behind the pause menu and the settings
+/// opened from it.
+fn draw_frozen(view: &mut PlayfieldView, field: &Playfield, resources: &Resources) {
+    view.draw(field, Vec2::ZERO, resources);
+    view.draw_scores(field, Vec2::ZERO);
+    draw_rectangle(
+        0.0,
+        0.0,
+        screen_width(),
+        screen_height(),
+        Color::new(0.0, 0.0, 0.0, 0.6),
     );
+}
 
-    let mut enemy_small_sprite = AnimatedSprite::new(
-        17,
-        16,
-        &[Animation {
-            name: "enemy_small".to_string(),
-            row: 0,
-            frames: 2,
-            fps: 12,
-        }],
-        true,
+/// A `width` wide window of buttons in `skin`, one per entry, with `>` next
+/// to the selected one. Returns the entry picked this frame.
+fn list_menu(
+    id: Id,
+    title: &str,
+    entries: &[&str],
+    width: f32,
+    selection: usize,
+    confirm: bool,
+    skin: &Skin,
+) -> Option<usize> {
+    let size = vec2(width, 70.0 + entries.len() as f32 * 50.0);
+    let mut chosen = None;
+    root_ui().push_skin(skin);
+    root_ui().window(
+        id,
+        vec2(
+            screen_width() / 2.0 - size.x / 2.0,
+            screen_height() / 2.0 - size.y / 2.0,
+        ),
+        size,
+        |ui| {
+            ui.label(vec2(80.0, -34.0), title);
+            ui.label(vec2(2.0, 10.0 + selection as f32 * 50.0), ">");
+            for (i, entry) in entries.iter().enumerate() {
+                if ui.button(vec2(20.0, 6.0 + i as f32 * 50.0), *entry)
+                    || (confirm && selection == i)
+                {
+                    chosen = Some(i);
+                }
+            }
+        },
     );
+    root_ui().pop_skin();
+    chosen
+}
 
-    let mut enemy_big_sprite = AnimatedSprite::new(
-        32,
-        32,
-        &[Animation {
-            name: "enemy_big".to_string(),
-            row: 0,
-            frames: 2,
-            fps: 12,
-        }],
-        true,
-    );
+/// Opens the window the way the settings left it. Vsync can only be chosen
+/// here.
+fn window_conf() -> Conf {
+    let settings = Config::saved_settings(&*Storages::open(std::env::args().skip(1)).config);
+    let (width, height) = settings.window_size();
+    let mut conf = Conf {
+        window_title: "MyGame".to_string(),
+        window_width: width as i32,
+        window_height: height as i32,
+        fullscreen: settings.fullscreen,
+        ..Default::default()
+    };
+    conf.platform.swap_interval = Some(if settings.vsync { 1 } else { 0 });
+    conf
+}
+
+#[macroquad::main(window_conf)]
+async fn main() -> Result<(), macroquad::Error> {
+    let mut field = Playfield::new(0, vec2(screen_width(), screen_height()));
+    let mut view = PlayfieldView::new();
+    let mut versus = Versus::new(0, vec2(screen_width() / 2.0, screen_height()));
+    let mut versus_views = [PlayfieldView::new(), PlayfieldView::new()];
+    let mut versus_events: [Vec<Event>; 2] = [vec![], vec![]];
+    let mut accumulator: f32 = 0.0;
+    let mut events: Vec<Event> = vec![];
+    let mut gamepads = Gamepads::new();
+    let mut touch = TouchControls::new();
+    let mut menu_selection: usize = 0;
+    let mut achievements = Achievements::new();
+    let mut run_tracker = RunTracker::new();
+    let mut storages = Storages::open(std::env::args().skip(1));
+    let (mut save, save_message) = SaveData::load(&mut *storages.data, &*storages.legacy);
+    let (mut config, config_message) = Config::load(&mut *storages.config, &*storages.legacy);
+    let (mut online, online_message) =
+        OnlineLeaderboard::new(&config.online_server, &*storages.data);
+    // The last run's until a new one starts
+    let (mut replay, replay_message) = match Replay::load(&*storages.data) {
+        Ok(replay) => (replay.unwrap_or(Replay::new(0, Vec2::ZERO)), None),
+        Err(err) => (
+            Replay::new(0, Vec2::ZERO),
+            Some(format!("Could not read the last replay: {err}")),
+        ),
+    };
+    for message in save_message
+        .iter()
+        .chain(&config_message)
+        .chain(&online_message)
+        .chain(&replay_message)
+    {
+        error!("{}", message);
+    }
+    let mut save_status = save_message
+        .or(config_message)
+        .or(online_message)
+        .or(replay_message);
+    // Page of the online scores shown
+    let mut online_page: usize = 0;
+    // The controls screen edits a copy until it is saved
+    let mut edited_bindings = config.bindings.clone();
+    let mut capturing: Option<(Scope, Action)> = None;
+    let mut controls_status = String::new();
+    // Vsync the window was opened with, changes only apply on restart
+    let started_vsync = config.settings.vsync;
+    // Whether the settings were opened from the pause menu, to go back there
+    let mut settings_from_pause = false;
+    let mut frame_limiter = FrameLimiter::new();
+    // Players from the last run still to enter a name, best first
+    let mut pending_names: Vec<usize> = vec![];
+    let mut name_input = String::new();
+    let mut leaderboard_highlight: Option<usize> = None;
+    // Board on the leaderboard and online scores screens
+    let mut shown_board = ENDLESS_BOARD.to_string();
+    // Whether the board picker leads to the online scores
+    let mut boards_online = false;
+    let mut run_mode = RunMode::Endless;
+    // Practice settings, kept through resets
+    let mut practice_speed = 2;
+    let mut practice_invincible = false;
+    // Level whose boss the practice menu sends in
+    let mut practice_boss: usize = 0;
+    let mut tutorial = Tutorial::new();
+    // How the last campaign level went, for the level complete screen
+    let mut rating = Rating {
+        hits_taken: 0,
+        time: 0.0,
+        par_time: 0.0,
+    };
+
+    // Load textures
+    set_pc_assets_folder("assets");
 
     let mut game_state = GameState::MainMenu;
-    let mut score: u32 = 0;
-    let mut high_score: u32 = fs::read_to_string("highscore.dat")
-        .map_or(Ok(0), |i| i.parse::<u32>())
-        .unwrap_or(0);
+
+    // Online play is started from the command line, see `NetOptions`
+    let mut net_game = None;
+    if let Some(options) = NetOptions::from_args(std::env::args().skip(1)) {
+        match NetGame::start(options) {
+            Ok(game) => {
+                net_game = Some(game);
+                game_state = GameState::Online;
+            }
+            Err(err) => error!("Could not start online game: {}", err),
+        }
+    }
 
     let mut direction_modifier: f32 = 0.0;
     #[cfg(not(target_arch = "wasm32"))]
@@ -330,6 +586,7 @@ async fn main() -> Result<(), macroquad::Error> {
             uniforms: vec![
                 UniformDesc::new("iResolution", UniformType::Float2),
                 UniformDesc::new("direction_modifier", UniformType::Float1),
+                UniformDesc::new("tint", UniformType::Float3),
             ],
             ..Default::default()
         },
@@ -337,6 +594,7 @@ async fn main() -> Result<(), macroquad::Error> {
 
     Resources::load().await?;
     let resources = storage::get::<Resources>();
+    let mut purchases = Purchases::new(&resources.shop_items);
 
     root_ui().push_skin(&resources.ui_skin);
 
@@ -344,17 +602,44 @@ async fn main() -> Result<(), macroquad::Error> {
         &resources.theme_music,
         PlaySoundParams {
             looped: true,
-            volume: 0.5,
+            volume: config.settings.music(true),
         },
     );
 
     loop {
         clear_background(BLACK);
+        gamepads.update();
+        touch.update();
+        online.update(&mut *storages.data);
+        // The music is turned down outside of a run
+        set_sound_volume(
+            &resources.theme_music,
+            config.settings.music(!matches!(
+                game_state,
+                GameState::Playing | GameState::Versus | GameState::Online
+            )),
+        );
 
         #[cfg(not(target_arch = "wasm32"))]
         material.set_uniform("iResolution", (screen_width(), screen_height()));
         #[cfg(not(target_arch = "wasm32"))]
         material.set_uniform("direction_modifier", direction_modifier);
+        // Campaign levels colour the stars while they are played
+        #[cfg(not(target_arch = "wasm32"))]
+        material.set_uniform(
+            "tint",
+            match (run_mode, &game_state) {
+                (
+                    RunMode::Campaign { level },
+                    GameState::Playing
+                    | GameState::Paused
+                    | GameState::Shop
+                    | GameState::GameOver
+                    | GameState::LevelComplete,
+                ) => resources.levels[level].tint,
+                _ => Vec3::ONE,
+            },
+        );
         #[cfg(not(target_arch = "wasm32"))]
         gl_use_material(&material);
         #[cfg(not(target_arch = "wasm32"))]
@@ -371,12 +656,22 @@ async fn main() -> Result<(), macroquad::Error> {
         #[cfg(not(target_arch = "wasm32"))]
         gl_use_default_material();
 
+        // Run picked on this frame's screen, set up below
+        let mut start: Option<RunMode> = None;
         match game_state {
             GameState::MainMenu => {
-                if is_key_pressed(KeyCode::Escape) {
+                if is_key_pressed(config.bindings.key(Scope::Global, Action::Back)) {
                     std::process::exit(0);
                 }
-                set_sound_volume(&resources.theme_music, 0.5);
+                let confirm =
+                    config
+                        .bindings
+                        .navigate_menu(&mut menu_selection, MAIN_MENU.len(), &gamepads);
+                // Squeeze the entries together on small screens
+                let spacing =
+                    ((screen_height() - 130.0) / MAIN_MENU.len() as f32).clamp(64.0, 85.0);
+                let window_size = vec2(370.0, 110.0 + MAIN_MENU.len() as f32 * spacing);
+                let mut chosen = None;
                 root_ui().window(
                     hash!(),
                     vec2(
@@ -386,208 +681,583 @@ async fn main() -> Result<(), macroquad::Error> {
                     window_size,
                     |ui| {
                         ui.label(vec2(80.0, -34.0), "Main Menu");
-                        if ui.button(vec2(65.0, 25.0), "Play") {
-                            squares.clear();
-                            bullets.clear();
-                            explosions.clear();
-                            circle.x = screen_width() / 2.0;
-                            circle.y = screen_height() / 2.0;
-                            exhaust.1 = vec2(circle.x, circle.y + circle.size / 2.0);
-                            game_state = GameState::Playing;
-                            score = 0;
-                        }
-                        if ui.button(vec2(65.0, 125.0), "Quit") {
-                            std::process::exit(0);
+                        ui.label(vec2(25.0, 45.0 + menu_selection as f32 * spacing), ">");
+                        for (i, label) in MAIN_MENU.iter().enumerate() {
+                            if ui.button(vec2(65.0, 25.0 + i as f32 * spacing), *label)
+                                || (confirm && menu_selection == i)
+                            {
+                                chosen = Some(i);
+                            }
                         }
                     },
                 );
+                if let Some(message) = &save_status {
+                    let text_dimensions = measure_text(message, None, 20, 1.0);
+                    draw_text(
+                        message,
+                        screen_width() / 2.0 - text_dimensions.width / 2.0,
+                        screen_height() - 30.0,
+                        20.0,
+                        RED,
+                    );
+                }
+                match chosen {
+                    Some(0) => {
+                        menu_selection = 0;
+                        game_state = if tutorial::offer(&save) {
+                            GameState::TutorialOffer
+                        } else {
+                            GameState::PlayMenu
+                        };
+                    }
+                    Some(1) => {
+                        // Starting the day's first run uses up its ranked
+                        // attempt, however it ends
+                        let day = daily::day(miniquad::date::now());
+                        let ranked = daily::attempt_left(&save, day);
+                        if ranked {
+                            daily::use_attempt(&mut save, day);
+                            write_save(&save, &mut *storages.data, &mut save_status);
+                        }
+                        start = Some(RunMode::Daily { day, ranked });
+                    }
+                    Some(2) => {
+                        versus = Versus::new(
+                            miniquad::date::now() as u64,
+                            vec2(screen_width() / 2.0, screen_height()),
+                        );
+                        for view in &mut versus_views {
+                            view.clear();
+                        }
+                        accumulator = 0.0;
+                        game_state = GameState::Versus;
+                    }
+                    Some(3) => {
+                        menu_selection = 0;
+                        game_state = GameState::Records;
+                    }
+                    Some(4) => {
+                        settings_from_pause = false;
+                        menu_selection = 0;
+                        game_state = GameState::Settings;
+                    }
+                    Some(5) => {
+                        edited_bindings = config.bindings.clone();
+                        capturing = None;
+                        controls_status.clear();
+                        menu_selection = 0;
+                        game_state = GameState::Controls;
+                    }
+                    Some(_) => std::process::exit(0),
+                    None => {}
+                }
             }
-
-            GameState::Playing => {
-                set_sound_volume(&resources.theme_music, 1.0);
-                let delta_time = get_frame_time();
-                ship_sprite.set_animation(0);
-                // Handle keyboard input
-                if is_key_down(KeyCode::Right) {
-                    circle.x += circle.speed * delta_time;
-                    exhaust.1.x = circle.x;
-                    direction_modifier += 0.05 * delta_time;
-                    ship_sprite.set_animation(2);
-                }
-                if is_key_down(KeyCode::Left) {
-                    circle.x -= circle.speed * delta_time;
-                    exhaust.1.x = circle.x;
-                    direction_modifier -= 0.05 * delta_time;
-                    ship_sprite.set_animation(1);
-                }
-                if is_key_down(KeyCode::Down) {
-                    circle.y += circle.speed * delta_time;
-                    exhaust.1.y = circle.y + circle.size / 2.0;
-                }
-                if is_key_down(KeyCode::Up) {
-                    circle.y -= circle.speed * delta_time;
-                    exhaust.1.y = circle.y + circle.size / 2.0;
-                }
-
-                // Shoot
-                if is_key_pressed(KeyCode::Space) {
-                    bullets.push(Shape {
-                        x: circle.x,
-                        y: circle.y - 24.0,
-                        speed: circle.speed * 2.0,
-                        size: 32.0,
-                        collided: false,
-                    });
-                    play_sound_once(&resources.sound_laser);
-                }
-
-                if is_key_pressed(KeyCode::Escape) {
-                    game_state = GameState::Paused;
-                }
-
-                // Keep circle on the screen
-                circle.x = clamp(
-                    circle.x,
-                    0.0 + (circle.size / 2.0),
-                    screen_width() - (circle.size / 2.0),
+            GameState::PlayMenu => {
+                let mut chosen = None;
+                if config.bindings.pressed(Action::Back, &gamepads) {
+                    chosen = Some(PLAY_MENU.len() - 1);
+                }
+                let confirm =
+                    config
+                        .bindings
+                        .navigate_menu(&mut menu_selection, PLAY_MENU.len(), &gamepads);
+                chosen = chosen.or(list_menu(
+                    hash!(),
+                    "Play",
+                    &PLAY_MENU,
+                    400.0,
+                    menu_selection,
+                    confirm,
+                    &resources.shop_skin,
+                ));
+                match chosen {
+                    Some(0) => start = Some(RunMode::Endless),
+                    Some(1) => {
+                        // Start on the furthest level unlocked
+                        menu_selection = (0..resources.levels.len())
+                            .rev()
+                            .find(|level| campaign::unlocked(&save, *level))
+                            .unwrap_or(0);
+                        game_state = GameState::LevelSelect;
+                    }
+                    Some(choice @ (2 | 3)) => {
+                        start = Some(RunMode::TimeAttack { limit: choice - 2 });
+                    }
+                    Some(4) => start = Some(RunMode::Pacifist),
+                    Some(5) => {
+                        practice_speed = 2;
+                        practice_invincible = false;
+                        start = Some(RunMode::Practice);
+                    }
+                    Some(6) => start = Some(RunMode::Tutorial),
+                    Some(_) => {
+                        menu_selection = 0;
+                        game_state = GameState::MainMenu;
+                    }
+                    None => {}
+                }
+            }
+            GameState::TutorialOffer => {
+                let mut chosen = None;
+                if config.bindings.pressed(Action::Back, &gamepads) {
+                    chosen = Some(1);
+                }
+                let confirm = config.bindings.navigate_menu(
+                    &mut menu_selection,
+                    TUTORIAL_OFFER.len(),
+                    &gamepads,
                 );
-                circle.y = clamp(
-                    circle.y,
-                    0.0 + (circle.size / 2.0),
-                    screen_height() - (circle.size / 2.0),
+                chosen = chosen.or(list_menu(
+                    hash!(),
+                    "New here?",
+                    &TUTORIAL_OFFER,
+                    400.0,
+                    menu_selection,
+                    confirm,
+                    &resources.shop_skin,
+                ));
+                match chosen {
+                    Some(0) => start = Some(RunMode::Tutorial),
+                    Some(_) => {
+                        // It's still in the play menu for later
+                        tutorial::remember(&mut save);
+                        write_save(&save, &mut *storages.data, &mut save_status);
+                        menu_selection = 0;
+                        game_state = GameState::PlayMenu;
+                    }
+                    None => {}
+                }
+            }
+            GameState::LevelSelect => {
+                let levels = &resources.levels;
+                let entries: Vec<String> = levels
+                    .iter()
+                    .enumerate()
+                    .map(|(i, level)| {
+                        if campaign::unlocked(&save, i) {
+                            let stars = campaign::best_stars(&save, i) as usize;
+                            format!("{} {} {}", i + 1, level.name, "*".repeat(stars))
+                        } else {
+                            format!("{} Locked", i + 1)
+                        }
+                    })
+                    .chain(["Back".to_string()])
+                    .collect();
+                let entries: Vec<&str> = entries.iter().map(String::as_str).collect();
+                let mut chosen = None;
+                if config.bindings.pressed(Action::Back, &gamepads) {
+                    chosen = Some(levels.len());
+                }
+                let confirm =
+                    config
+                        .bindings
+                        .navigate_menu(&mut menu_selection, entries.len(), &gamepads);
+                chosen = chosen.or(list_menu(
+                    hash!(),
+                    "Campaign",
+                    &entries,
+                    600.0,
+                    menu_selection,
+                    confirm,
+                    &resources.shop_skin,
+                ));
+                match chosen {
+                    Some(level) if level < levels.len() && campaign::unlocked(&save, level) => {
+                        start = Some(RunMode::Campaign { level });
+                    }
+                    // Locked levels can't be picked
+                    Some(level) if level < levels.len() => {}
+                    Some(_) => {
+                        menu_selection = 1;
+                        game_state = GameState::PlayMenu;
+                    }
+                    None => {}
+                }
+            }
+            GameState::LevelComplete => {
+                let RunMode::Campaign { level } = run_mode else {
+                    unreachable!("only campaign levels are completed");
+                };
+                if config.bindings.pressed(Action::Confirm, &gamepads)
+                    || config.bindings.pressed(Action::Back, &gamepads)
+                    || touch.tapped()
+                {
+                    // Move on to the next level, or stay on the last
+                    menu_selection = (level + 1).min(resources.levels.len() - 1);
+                    game_state = GameState::LevelSelect;
+                }
+                campaign::draw_complete(
+                    &resources.levels[level],
+                    level + 1,
+                    rating,
+                    &resources.font,
                 );
+            }
+            GameState::Controls => {
+                // Every binding in menu order, then Save, Defaults and Back
+                let cells: Vec<(Scope, Action)> = Action::SHIP
+                    .iter()
+                    .flat_map(|action| (0..MAX_PLAYERS).map(|slot| (Scope::Player(slot), *action)))
+                    .chain(Action::GLOBAL.iter().map(|action| (Scope::Global, *action)))
+                    .collect();
+                let cell_position = |scope: Scope, action: Action| match scope {
+                    Scope::Player(slot) => {
+                        let row = Action::SHIP.iter().position(|a| *a == action).unwrap();
+                        vec2(280.0 + slot as f32 * 220.0, 50.0 + row as f32 * 30.0)
+                    }
+                    Scope::Global => {
+                        let row = Action::GLOBAL.iter().position(|a| *a == action).unwrap();
+                        vec2(280.0, 50.0 + (Action::SHIP.len() + row) as f32 * 30.0)
+                    }
+                };
+                // The control scheme sits in its own row below the bindings
+                let scheme_row = Action::SHIP.len() + Action::GLOBAL.len();
+                let scheme_entry = cells.len();
+                let footer_entry = cells.len() + 1;
+                let footer_y = 60.0 + (scheme_row + 1) as f32 * 30.0;
 
-                // Generate a new square
-                if rand::gen_range(0, 99) >= 95 {
-                    let size = rand::gen_range(16.0, 64.0);
-                    squares.push(Shape {
-                        size,
-                        speed: rand::gen_range(50.0, 150.0),
-                        x: rand::gen_range(size / 2.0, screen_width() - size / 2.0),
-                        y: -size,
-                        collided: false,
-                    });
-                }
-
-                // Move squares
-                for square in &mut squares {
-                    square.y += square.speed * delta_time;
-                }
-
-                // Move bullets
-                for bullet in &mut bullets {
-                    bullet.y -= bullet.speed * delta_time;
-                }
-
-                // Update animation
-                ship_sprite.update();
-                bullet_sprite.update();
-                enemy_small_sprite.update();
-                enemy_big_sprite.update();
-
-                // Remove squares and bullets when they go off screen or have collided
-                squares.retain(|square| square.y < screen_height() + square.size);
-                bullets.retain(|bullet| bullet.y > 0.0 - bullet.size / 2.0);
-                squares.retain(|square| !square.collided);
-                bullets.retain(|bullet| !bullet.collided);
-                explosions.retain(|(explosion, _)| explosion.config.emitting);
-
-                // Check collisions
-                if squares.iter().any(|square| circle.collides_with(square)) {
-                    if score == high_score {
-                        fs::write("highscore.dat", high_score.to_string()).ok();
-                    }
-                    game_state = GameState::GameOver;
-                }
-
-                for square in squares.iter_mut() {
-                    for bullet in bullets.iter_mut() {
-                        if bullet.collides_with(&square) {
-                            bullet.collided = true;
-                            square.collided = true;
-                            score += square.size.round() as u32;
-                            high_score = high_score.max(score);
-                            explosions.push((
-                                Emitter::new(EmitterConfig {
-                                    amount: square.size.round() as u32 * 4,
-                                    texture: Some(resources.explosion_texture.clone()),
-                                    lifetime: square.size * 0.0133,
-                                    ..particle_explosion()
-                                }),
-                                vec2(square.x, square.y),
-                            ));
-                            play_sound_once(&resources.sound_explosion);
+                let mut confirm = false;
+                let mut leave = false;
+                if let Some((scope, action)) = capturing {
+                    // Escape backs out, so it only comes back as a binding
+                    // through Defaults or the config file
+                    if is_key_pressed(KeyCode::Escape) || gamepads.any_pressed(PadButton::East) {
+                        capturing = None;
+                        controls_status.clear();
+                    } else if let Some(key) = get_last_key_pressed() {
+                        if bindings::bindable(key) {
+                            edited_bindings.set(scope, action, key);
+                            capturing = None;
+                            controls_status.clear();
+                        } else {
+                            controls_status = format!("{key:?} can't be bound");
                         }
                     }
+                } else {
+                    confirm = config.bindings.navigate_menu(
+                        &mut menu_selection,
+                        footer_entry + 3,
+                        &gamepads,
+                    );
+                    leave = config.bindings.pressed(Action::Back, &gamepads);
                 }
 
-                // Draw everything
-                let ship_frame = ship_sprite.frame();
-                draw_texture_ex(
-                    &resources.ship_texture,
-                    circle.x - ship_frame.dest_size.x,
-                    circle.y - ship_frame.dest_size.y,
-                    WHITE,
-                    DrawTextureParams {
-                        dest_size: Some(ship_frame.dest_size * 2.0),
-                        source: Some(ship_frame.source_rect),
-                        ..Default::default()
+                let conflicts = edited_bindings.conflicts();
+                let controls_size = vec2(760.0, footer_y + 90.0);
+                let mut apply = false;
+                root_ui().push_skin(&resources.shop_skin);
+                root_ui().window(
+                    hash!(),
+                    vec2(
+                        screen_width() / 2.0 - controls_size.x / 2.0,
+                        screen_height() / 2.0 - controls_size.y / 2.0,
+                    ),
+                    controls_size,
+                    |ui| {
+                        ui.label(vec2(80.0, -34.0), "Controls");
+                        for slot in 0..MAX_PLAYERS {
+                            ui.label(
+                                vec2(280.0 + slot as f32 * 220.0, 10.0),
+                                &format!("P{}", slot + 1),
+                            );
+                        }
+                        for (row, action) in Action::SHIP.iter().chain(&Action::GLOBAL).enumerate()
+                        {
+                            ui.label(vec2(20.0, 50.0 + row as f32 * 30.0), action.name());
+                        }
+                        for (i, (scope, action)) in cells.iter().enumerate() {
+                            let position = cell_position(*scope, *action);
+                            if menu_selection == i {
+                                ui.label(position - vec2(18.0, 0.0), ">");
+                            }
+                            let mut label = if capturing == Some((*scope, *action)) {
+                                "...".to_string()
+                            } else {
+                                format!("{:?}", edited_bindings.key(*scope, *action))
+                            };
+                            if conflicts.contains(&(*scope, *action)) {
+                                label.push_str(" !");
+                            }
+                            if ui.button(position - vec2(0.0, 4.0), label.as_str())
+                                || (confirm && menu_selection == i)
+                            {
+                                capturing = Some((*scope, *action));
+                                controls_status =
+                                    format!("Press a key for {}, Escape to cancel", action.name());
+                            }
+                        }
+                        let scheme_y = 50.0 + scheme_row as f32 * 30.0;
+                        ui.label(vec2(20.0, scheme_y), "Scheme");
+                        if menu_selection == scheme_entry {
+                            ui.label(vec2(262.0, scheme_y), ">");
+                        }
+                        if ui.button(vec2(280.0, scheme_y - 4.0), edited_bindings.scheme.name())
+                            || (confirm && menu_selection == scheme_entry)
+                        {
+                            edited_bindings.scheme = edited_bindings.scheme.next();
+                        }
+                        for (i, label) in ["Save", "Defaults", "Back"].iter().enumerate() {
+                            let position = vec2(20.0 + i as f32 * 240.0, footer_y);
+                            if menu_selection == footer_entry + i {
+                                ui.label(position - vec2(18.0, -4.0), ">");
+                            }
+                            if ui.button(position, *label)
+                                || (confirm && menu_selection == footer_entry + i)
+                            {
+                                match i {
+                                    0 => apply = true,
+                                    1 => edited_bindings = Bindings::default(),
+                                    _ => leave = true,
+                                }
+                            }
+                        }
+                        ui.label(vec2(20.0, footer_y + 45.0), &controls_status);
                     },
                 );
-                exhaust.0.draw(exhaust.1);
-
-                for square in &squares {
-                    match square.size {
-                        size if size > 50.0 => {
-                            let enemy_frame = enemy_big_sprite.frame();
-                            draw_texture_ex(
-                                &resources.enemy_big_texture,
-                                square.x - square.size / 2.0,
-                                square.y - square.size / 2.0,
-                                WHITE,
-                                DrawTextureParams {
-                                    dest_size: Some(vec2(square.size, square.size)),
-                                    source: Some(enemy_frame.source_rect),
-                                    ..Default::default()
-                                },
+                root_ui().pop_skin();
+
+                if apply {
+                    if !conflicts.is_empty() {
+                        controls_status = "Keys marked ! are used twice".to_string();
+                    } else {
+                        config.bindings = edited_bindings.clone();
+                        controls_status = match config.write(&mut *storages.config) {
+                            Ok(()) => "Saved".to_string(),
+                            Err(err) => format!("Could not save: {err}"),
+                        };
+                    }
+                }
+                if leave {
+                    menu_selection = 0;
+                    game_state = GameState::MainMenu;
+                }
+            }
+
+            GameState::Settings => {
+                if settings_from_pause {
+                    draw_frozen(&mut view, &field, &resources);
+                }
+                let back_entry = Setting::ALL.len();
+                let confirm =
+                    config
+                        .bindings
+                        .navigate_menu(&mut menu_selection, back_entry + 1, &gamepads);
+                let mut leave = config.bindings.pressed(Action::Back, &gamepads);
+                // Left and right step the selected setting, confirm only
+                // works on Back
+                let step = config.bindings.menu_sideways(&gamepads);
+                let mut stepped = Setting::ALL
+                    .get(menu_selection)
+                    .filter(|_| step != 0)
+                    .map(|setting| (*setting, step > 0));
+
+                let settings_size = vec2(620.0, 70.0 + (back_entry + 1) as f32 * 50.0);
+                root_ui().window(
+                    hash!(),
+                    vec2(
+                        screen_width() / 2.0 - settings_size.x / 2.0,
+                        screen_height() / 2.0 - settings_size.y / 2.0,
+                    ),
+                    settings_size,
+                    |ui| {
+                        ui.label(vec2(80.0, -34.0), "Settings");
+                        ui.label(vec2(2.0, 10.0 + menu_selection as f32 * 50.0), ">");
+                        for (row, setting) in Setting::ALL.iter().enumerate() {
+                            let y = row as f32 * 50.0;
+                            ui.label(vec2(40.0, 10.0 + y), setting.name());
+                            if ui.button(vec2(270.0, 6.0 + y), "<") {
+                                stepped = Some((*setting, false));
+                            }
+                            ui.label(
+                                vec2(330.0, 10.0 + y),
+                                &setting.value(&config, started_vsync),
                             );
+                            if ui.button(vec2(550.0, 6.0 + y), ">") {
+                                stepped = Some((*setting, true));
+                            }
                         }
-                        _ => {
-                            let enemy_frame = enemy_small_sprite.frame();
-                            draw_texture_ex(
-                                &resources.enemy_small_texture,
-                                square.x - square.size / 2.0,
-                                square.y - square.size / 2.0,
-                                WHITE,
-                                DrawTextureParams {
-                                    dest_size: Some(vec2(square.size, square.size)),
-                                    source: Some(enemy_frame.source_rect),
-                                    ..Default::default()
-                                },
-                            );
+                        if ui.button(vec2(20.0, 6.0 + back_entry as f32 * 50.0), "Back")
+                            || (confirm && menu_selection == back_entry)
+                        {
+                            leave = true;
                         }
+                    },
+                );
+
+                if let Some((setting, up)) = stepped {
+                    setting.step(&mut config, up);
+                }
+                if leave {
+                    if let Err(err) = config.write(&mut *storages.config) {
+                        let message = format!("Could not save settings: {err}");
+                        error!("{}", message);
+                        save_status = Some(message);
+                    }
+                    if settings_from_pause {
+                        menu_selection = 2;
+                        game_state = GameState::Paused;
+                    } else {
+                        menu_selection = 4;
+                        game_state = GameState::MainMenu;
                     }
                 }
+            }
 
-                let bullet_frame = bullet_sprite.frame();
-                for bullet in &bullets {
-                    draw_texture_ex(
-                        &resources.bullet_texture,
-                        bullet.x - bullet.size / 2.0,
-                        bullet.y - bullet.size / 2.0,
-                        WHITE,
-                        DrawTextureParams {
-                            dest_size: Some(vec2(bullet.size, bullet.size)),
-                            source: Some(bullet_frame.source_rect),
-                            ..Default::default()
-                        },
-                    )
+            GameState::Playing => {
+                let size = vec2(screen_width(), screen_height());
+                if field.size != size {
+                    field.size = size;
+                    replay.resize(size);
+                }
+
+                // Let another player drop in on their fire key or button
+                let next_slot = field.players.len();
+                if next_slot < MAX_PLAYERS
+                    && config
+                        .bindings
+                        .player_pressed(next_slot, Action::Fire, &gamepads)
+                {
+                    field.join();
+                    replay.join();
+                }
+
+                let mut inputs: Vec<PlayerInput> = (0..field.players.len())
+                    .map(|slot| config.bindings.input(slot, &gamepads))
+                    .collect();
+                // Touch and the mouse steer the first ship
+                let ship = field
+                    .players
+                    .first()
+                    .map(|player| (vec2(player.ship.x, player.ship.y), player.ship.speed));
+                inputs[0] = inputs[0]
+                    .merge(touch.input(ship))
+                    .merge(mouse::input(config.bindings.scheme, ship));
+
+                // Run the simulation in fixed ticks
+                events.clear();
+                let speed = match run_mode {
+                    RunMode::Practice => PRACTICE_SPEEDS[practice_speed],
+                    _ => 1.0,
+                };
+                accumulator = (accumulator + get_frame_time() * speed).min(0.25);
+                while accumulator >= TICK {
+                    accumulator -= TICK;
+                    field.step(&inputs, &mut events);
+                    replay.tick(&inputs);
+                    for input in &inputs {
+                        direction_modifier += input.movement().x * 0.05 * TICK;
+                    }
+                }
+                view.handle(&events, &resources, &config.settings);
+                view.update(&field);
+                // Practice and the tutorial don't count towards anything
+                let recorded = !matches!(run_mode, RunMode::Practice | RunMode::Tutorial);
+                run_tracker.update(&field, &events);
+                if recorded && achievements.update(&field, &events, &run_tracker, &mut save) {
+                    write_save(&save, &mut *storages.data, &mut save_status);
+                }
+
+                // Pause on request, or when a playing pad is unplugged
+                let unplugged = gamepads.events().iter().any(|event| {
+                    matches!(event, PadEvent::Disconnected(slot) if *slot < field.players.len())
+                });
+                if config.bindings.pressed(Action::Pause, &gamepads)
+                    || touch.pause_tapped()
+                    || unplugged
+                {
+                    game_state = if run_mode == RunMode::Practice {
+                        menu_selection = 0;
+                        GameState::PracticeMenu
+                    } else {
+                        tutorial.paused();
+                        menu_selection = 0;
+                        GameState::Paused
+                    };
+                }
+
+                if run_mode == RunMode::Tutorial {
+                    tutorial.update(&mut field, &events);
+                    if tutorial.finished() {
+                        tutorial::remember(&mut save);
+                        write_save(&save, &mut *storages.data, &mut save_status);
+                        menu_selection = 0;
+                        game_state = GameState::PlayMenu;
+                    }
+                } else if !recorded {
+                    // Start over straight away
+                    if field.game_over() {
+                        start = Some(run_mode);
+                    }
+                } else if field.game_over() {
+                    // Only a finished run counts towards the high score, not
+                    // one quit from the pause menu
+                    run_tracker.record(&mut save);
+                    if run_mode.sets_high_score() {
+                        save.high_score = save.high_score.max(field.best_score());
+                    }
+                    write_save(&save, &mut *storages.data, &mut save_status);
+                    write_replay(&replay, &mut *storages.data, &mut save_status);
+                    game_state = if field.time_left.is_some() {
+                        GameState::TimeUp
+                    } else {
+                        GameState::GameOver
+                    };
+                } else if field.level_cleared {
+                    run_tracker.record(&mut save);
+                    if run_mode.sets_high_score() {
+                        save.high_score = save.high_score.max(field.best_score());
+                    }
+                    if let RunMode::Campaign { level } = run_mode {
+                        rating = Rating {
+                            hits_taken: run_tracker
+                                .players
+                                .iter()
+                                .map(|stats| stats.hits_taken)
+                                .sum(),
+                            time: field.tick as f32 * TICK,
+                            par_time: resources.levels[level].par_time,
+                        };
+                        campaign::record_clear(&mut save, level, rating);
+                    }
+                    write_save(&save, &mut *storages.data, &mut save_status);
+                    write_replay(&replay, &mut *storages.data, &mut save_status);
+                    game_state = GameState::LevelComplete;
+                } else if field.wave_cleared {
+                    if let RunMode::TimeAttack { .. } = run_mode {
+                        // No shopping on the clock
+                        field.start_next_wave();
+                        replay.next_wave();
+                    } else {
+                        game_state = GameState::Shop;
+                    }
                 }
 
-                for (explosion, coords) in explosions.iter_mut() {
-                    explosion.draw(*coords)
+                // Draw everything
+                view.draw(&field, Vec2::ZERO, &resources);
+                view.draw_scores(&field, Vec2::ZERO);
+                if field.players.len() < MAX_PLAYERS {
+                    let join_button = if gamepads.connected(next_slot) {
+                        "A".to_string()
+                    } else {
+                        format!(
+                            "{:?}",
+                            config.bindings.key(Scope::Player(next_slot), Action::Fire)
+                        )
+                    };
+                    draw_text(
+                        format!("P{} press {join_button} to join", next_slot + 1).as_str(),
+                        10.0,
+                        screen_height() - 15.0,
+                        25.0,
+                        GRAY,
+                    );
                 }
-                draw_text(format!("Score {score}").as_str(), 10.0, 35.0, 25.0, WHITE);
+                // The run's score joins the high score once it is over
+                let high_score = if run_mode.sets_high_score() {
+                    save.high_score.max(field.best_score())
+                } else {
+                    save.high_score
+                };
                 let highscore_text = format!("High Score {high_score}");
                 let text_dimensions = measure_text(highscore_text.as_str(), None, 25, 1.0);
                 draw_text(
@@ -597,38 +1267,361 @@ async fn main() -> Result<(), macroquad::Error> {
                     25.0,
                     WHITE,
                 );
-            }
-            GameState::Paused => {
-                if is_key_pressed(KeyCode::Space) {
-                    game_state = GameState::Playing;
-                }
-                set_sound_volume(&resources.theme_music, 0.5);
-                let text = "Paused";
-                let text_dimensions = measure_text(text, None, 50, 1.0);
+                // Pacifist runs have no waves or credits, just the time
+                let wave_text = if field.pacifist {
+                    format!(
+                        "Time {}",
+                        leaderboard::format_duration(field.tick as f32 * TICK)
+                    )
+                } else {
+                    format!("Wave {}  Credits {}", field.wave, field.credits)
+                };
+                let text_dimensions = measure_text(wave_text.as_str(), None, 25, 1.0);
                 draw_text(
-                    text,
-                    screen_width() / 2.0 - text_dimensions.width / 2.0,
-                    screen_height() / 2.0,
-                    50.0,
+                    wave_text.as_str(),
+                    screen_width() - text_dimensions.width - 10.0,
+                    60.0,
+                    25.0,
                     WHITE,
                 );
+                let mode_text = match run_mode {
+                    RunMode::Endless => None,
+                    RunMode::Daily { day, ranked: true } => {
+                        Some(format!("Daily {}", daily::date(day)))
+                    }
+                    RunMode::Daily { day, ranked: false } => {
+                        Some(format!("Daily {}  Unranked", daily::date(day)))
+                    }
+                    RunMode::Campaign { level } => Some(format!(
+                        "Level {} {}",
+                        level + 1,
+                        resources.levels[level].name
+                    )),
+                    RunMode::TimeAttack { limit } => Some(format!(
+                        "Time Attack {}",
+                        leaderboard::format_duration(time_attack::seconds(limit))
+                    )),
+                    RunMode::Pacifist => Some("Pacifist".to_string()),
+                    RunMode::Tutorial => None,
+                    RunMode::Practice => Some(format!(
+                        "Practice {}x{}  {:?} for menu",
+                        PRACTICE_SPEEDS[practice_speed],
+                        if field.invincible {
+                            "  Invulnerable"
+                        } else {
+                            ""
+                        },
+                        config.bindings.key(Scope::Global, Action::Pause)
+                    )),
+                };
+                if let Some(mode_text) = mode_text {
+                    let text_dimensions = measure_text(mode_text.as_str(), None, 25, 1.0);
+                    draw_text(
+                        mode_text.as_str(),
+                        screen_width() - text_dimensions.width - 10.0,
+                        85.0,
+                        25.0,
+                        YELLOW,
+                    );
+                }
+                if let Some(time_left) = field.time_left {
+                    // Counted up so the clock reads 0:00 only once time is up
+                    let seconds = (time_left as f32 * TICK).ceil();
+                    let clock = leaderboard::format_duration(seconds);
+                    let font = Some(&resources.font);
+                    let dimensions = measure_text(&clock, font, 40, 1.0);
+                    draw_text_ex(
+                        &clock,
+                        screen_width() / 2.0 - dimensions.width / 2.0,
+                        50.0,
+                        TextParams {
+                            font,
+                            font_size: 40,
+                            color: if seconds <= 10.0 { RED } else { WHITE },
+                            ..Default::default()
+                        },
+                    );
+                }
+                if run_mode == RunMode::Tutorial {
+                    tutorial.draw(&config.bindings, gamepads.connected(0), &resources.font);
+                }
+                touch.draw();
             }
-            GameState::GameOver => {
-                if is_key_pressed(KeyCode::Space) {
-                    game_state = GameState::MainMenu;
+            GameState::Shop => {
+                let items = &resources.shop_items;
+                let shop_size = vec2(560.0, 120.0 + items.len() as f32 * 50.0);
+                let mut next_wave = config.bindings.pressed(Action::Back, &gamepads)
+                    || config.bindings.pressed(Action::Pause, &gamepads);
+                // The last entry is "Next Wave"
+                let confirm =
+                    config
+                        .bindings
+                        .navigate_menu(&mut menu_selection, items.len() + 1, &gamepads);
+                root_ui().push_skin(&resources.shop_skin);
+                root_ui().window(
+                    hash!(),
+                    vec2(
+                        screen_width() / 2.0 - shop_size.x / 2.0,
+                        screen_height() / 2.0 - shop_size.y / 2.0,
+                    ),
+                    shop_size,
+                    |ui| {
+                        ui.label(vec2(80.0, -34.0), &format!("Wave {} cleared", field.wave));
+                        ui.label(vec2(20.0, 10.0), &format!("Credits {}", field.credits));
+                        ui.label(vec2(2.0, 50.0 + menu_selection as f32 * 50.0), ">");
+                        for (i, item) in items.iter().enumerate() {
+                            let y = 50.0 + i as f32 * 50.0;
+                            let count = purchases.count(i);
+                            ui.label(vec2(20.0, y), &format!("{} ({count})", item.name));
+                            if item.sold_out(count) {
+                                ui.label(vec2(360.0, y), "Sold out");
+                                continue;
+                            }
+                            let price = item.price(count);
+                            let clicked =
+                                ui.button(vec2(360.0, y - 4.0), format!("Buy {price}").as_str());
+                            if (clicked || (confirm && menu_selection == i))
+                                && field.credits >= price
+                            {
+                                field.credits -= price;
+                                purchases.record(i);
+                                replay.buy(i);
+                                // Consumables go to every player, reviving any
+                                // that were knocked out
+                                for player in field.players.iter_mut() {
+                                    match item.upgrade {
+                                        Upgrade::Bomb => player.bombs += item.value as u32,
+                                        Upgrade::ExtraLife => player.lives += item.value as u32,
+                                        Upgrade::FireRate
+                                        | Upgrade::Speed
+                                        | Upgrade::ShieldCapacity => {}
+                                    }
+                                }
+                            }
+                        }
+                        if ui.button(vec2(20.0, 50.0 + items.len() as f32 * 50.0), "Next Wave")
+                            || (confirm && menu_selection == items.len())
+                        {
+                            next_wave = true;
+                        }
+                    },
+                );
+                root_ui().pop_skin();
+
+                if next_wave {
+                    let speed = MOVEMENT_SPEED
+                        * (1.0 + purchases.total(&resources.shop_items, Upgrade::Speed));
+                    let shield =
+                        purchases.total(&resources.shop_items, Upgrade::ShieldCapacity) as u32;
+                    let fire_rate = purchases.total(&resources.shop_items, Upgrade::FireRate);
+                    field.fire_cooldown = FIRE_COOLDOWN * (1.0 - fire_rate).max(0.1);
+                    for player in field.players.iter_mut() {
+                        player.ship.speed = speed;
+                        player.shield = shield;
+                    }
+                    field.start_next_wave();
+                    replay.next_wave();
+                    menu_selection = 0;
+                    game_state = GameState::Playing;
+                }
+            }
+            GameState::PracticeMenu => {
+                let boss = resources.levels.get(practice_boss);
+                let entries = [
+                    "Resume".to_string(),
+                    "Small Enemy".to_string(),
+                    "Big Enemy".to_string(),
+                    "Garbage".to_string(),
+                    format!("Wave {}", field.wave),
+                    boss.map_or("No Bosses".to_string(), |level| {
+                        format!("Boss {}", level.name)
+                    }),
+                    format!(
+                        "Invulnerable {}",
+                        if field.invincible { "On" } else { "Off" }
+                    ),
+                    format!("Speed {}x", PRACTICE_SPEEDS[practice_speed]),
+                    "Reset".to_string(),
+                    "Quit".to_string(),
+                ];
+                let entries: Vec<&str> = entries.iter().map(String::as_str).collect();
+                let mut chosen = None;
+                if config.bindings.pressed(Action::Back, &gamepads)
+                    || config.bindings.pressed(Action::Pause, &gamepads)
+                {
+                    chosen = Some(0);
                 }
-                let text = "GAME OVER!";
+                let confirm =
+                    config
+                        .bindings
+                        .navigate_menu(&mut menu_selection, entries.len(), &gamepads);
+                // Left and right pick the wave, boss and speed
+                let step = config.bindings.menu_sideways(&gamepads);
+                let cycle = |value: usize, count: usize| {
+                    (value as i32 + step).rem_euclid(count.max(1) as i32) as usize
+                };
+                match menu_selection {
+                    4 => field.wave = (field.wave as i32 + step).max(1) as u32,
+                    5 => practice_boss = cycle(practice_boss, resources.levels.len()),
+                    7 => practice_speed = cycle(practice_speed, PRACTICE_SPEEDS.len()),
+                    _ => {}
+                }
+
+                view.draw(&field, Vec2::ZERO, &resources);
+                view.draw_scores(&field, Vec2::ZERO);
+                chosen = chosen.or(list_menu(
+                    hash!(),
+                    "Practice",
+                    &entries,
+                    500.0,
+                    menu_selection,
+                    confirm,
+                    &resources.shop_skin,
+                ));
+                // Spawns resume play so they can be watched
+                match chosen {
+                    Some(0) => game_state = GameState::Playing,
+                    Some(1) => {
+                        field.spawn_enemy((16.0, BIG_ENEMY_SIZE), (50.0, 150.0));
+                        game_state = GameState::Playing;
+                    }
+                    Some(2) => {
+                        field.spawn_enemy((BIG_ENEMY_SIZE, 64.0), (50.0, 150.0));
+                        game_state = GameState::Playing;
+                    }
+                    Some(3) => {
+                        field.incoming_garbage += 5;
+                        game_state = GameState::Playing;
+                    }
+                    Some(4) => {
+                        field.send_wave();
+                        game_state = GameState::Playing;
+                    }
+                    Some(5) => {
+                        if let Some(level) = boss {
+                            field.spawn_boss(level.boss);
+                            game_state = GameState::Playing;
+                        }
+                    }
+                    Some(6) => {
+                        field.invincible = !field.invincible;
+                        practice_invincible = field.invincible;
+                    }
+                    Some(7) => practice_speed = (practice_speed + 1) % PRACTICE_SPEEDS.len(),
+                    Some(8) => start = Some(RunMode::Practice),
+                    Some(_) => {
+                        menu_selection = 0;
+                        game_state = GameState::MainMenu;
+                    }
+                    None => {}
+                }
+            }
+            GameState::Paused => {
+                draw_frozen(&mut view, &field, &resources);
+                if run_mode == RunMode::Tutorial {
+                    tutorial.draw(&config.bindings, gamepads.connected(0), &resources.font);
+                }
+
+                let mut chosen = None;
+                if config.bindings.pressed(Action::Pause, &gamepads)
+                    || config.bindings.pressed(Action::Back, &gamepads)
+                    || touch.pause_tapped()
+                {
+                    chosen = Some(0);
+                }
+                let confirm =
+                    config
+                        .bindings
+                        .navigate_menu(&mut menu_selection, PAUSE_MENU.len(), &gamepads);
+                chosen = chosen.or(list_menu(
+                    hash!(),
+                    "Paused",
+                    &PAUSE_MENU,
+                    400.0,
+                    menu_selection,
+                    confirm,
+                    &resources.shop_skin,
+                ));
+                match chosen {
+                    Some(0) => game_state = GameState::Playing,
+                    Some(1) => {
+                        start = Some(match run_mode {
+                            // This run already used the day's ranked attempt
+                            RunMode::Daily { day, .. } => RunMode::Daily { day, ranked: false },
+                            mode => mode,
+                        })
+                    }
+                    Some(2) => {
+                        settings_from_pause = true;
+                        menu_selection = 0;
+                        game_state = GameState::Settings;
+                    }
+                    Some(_) => {
+                        menu_selection = 0;
+                        game_state = GameState::MainMenu;
+                    }
+                    None => {}
+                }
+            }
+            GameState::GameOver | GameState::TimeUp => {
+                if config.bindings.pressed(Action::Confirm, &gamepads)
+                    || config.bindings.pressed(Action::Back, &gamepads)
+                    || touch.tapped()
+                {
+                    // Every scoring run of a ranked mode may make the
+                    // online board
+                    let board = run_mode.board();
+                    pending_names = (0..field.players.len())
+                        .filter(|i| {
+                            let score = field.players[*i].score;
+                            board.as_ref().is_some_and(|board| {
+                                save.board(board).qualifies(score)
+                                    || (online.enabled() && score > 0)
+                            })
+                        })
+                        .collect();
+                    pending_names.sort_by_key(|i| std::cmp::Reverse(field.players[*i].score));
+                    leaderboard_highlight = None;
+                    if let RunMode::Campaign { level } = run_mode {
+                        // Back to try the level again
+                        menu_selection = level;
+                        game_state = GameState::LevelSelect;
+                    } else if pending_names.is_empty() {
+                        game_state = GameState::MainMenu;
+                    } else {
+                        // Drop anything typed during play
+                        clear_input_queue();
+                        game_state = GameState::NameEntry;
+                    }
+                }
+                let (text, color) = match game_state {
+                    GameState::TimeUp => ("TIME UP!", YELLOW),
+                    _ => ("GAME OVER!", RED),
+                };
                 let text_dimensions = measure_text(text, None, 50, 1.0);
                 draw_text(
                     text,
                     screen_width() / 2.0 - text_dimensions.width / 2.0,
                     screen_height() / 2.0,
                     50.0,
-                    RED,
+                    color,
                 );
 
-                if score == high_score {
-                    let congratulation_text = format!("NEW HIGH SCORE: {high_score}");
+                for (i, player) in field.players.iter().enumerate() {
+                    let label = player.label(field.players.len());
+                    let score_text = format!("{label}Score {}", player.score);
+                    let score_text_dimensions = measure_text(score_text.as_str(), None, 25, 1.0);
+                    draw_text(
+                        score_text.as_str(),
+                        screen_width() / 2.0 - score_text_dimensions.width / 2.0,
+                        screen_height() / 2.0 + 100.0 + i as f32 * 30.0,
+                        25.0,
+                        player.color(),
+                    );
+                }
+
+                if run_mode.sets_high_score() && field.best_score() == save.high_score {
+                    let congratulation_text = format!("NEW HIGH SCORE: {}", save.high_score);
                     let congratulation_text_dimensions =
                         measure_text(congratulation_text.as_str(), None, 50, 1.0);
                     draw_text(
@@ -639,9 +1632,477 @@ async fn main() -> Result<(), macroquad::Error> {
                         RED,
                     );
                 }
+                stats::draw_run(
+                    &run_tracker,
+                    &field,
+                    screen_height() / 2.0 - 230.0,
+                    &resources.font,
+                );
+            }
+            GameState::NameEntry => {
+                let player = &field.players[pending_names[0]];
+
+                // Type on the keyboard, or pick letters arcade-style on a pad
+                let mut typed: Vec<char> = std::iter::from_fn(get_char_pressed).collect();
+                typed.reverse();
+                for character in typed.into_iter().filter_map(leaderboard::name_char) {
+                    if name_input.len() < MAX_NAME_LENGTH {
+                        name_input.push(character);
+                    }
+                }
+                let direction = gamepads.menu_direction();
+                if (is_key_pressed(KeyCode::Backspace) || direction.x < 0) && !name_input.is_empty()
+                {
+                    name_input.pop();
+                }
+                if direction.x > 0 && name_input.len() < MAX_NAME_LENGTH {
+                    name_input.push('A');
+                }
+                if direction.y != 0 {
+                    let last = name_input.pop().unwrap_or('A');
+                    let letters: Vec<char> = ('A'..='Z').chain('0'..='9').chain([' ']).collect();
+                    let index = letters.iter().position(|c| *c == last).unwrap_or(0) as i32;
+                    let index = (index - direction.y).rem_euclid(letters.len() as i32);
+                    name_input.push(letters[index as usize]);
+                }
+
+                if is_key_pressed(KeyCode::Enter)
+                    || gamepads.any_pressed(PadButton::Start)
+                    || gamepads.any_pressed(PadButton::South)
+                    || touch.tapped()
+                {
+                    let name = name_input.trim();
+                    let entry = Entry {
+                        name: if name.is_empty() { "???" } else { name }.to_string(),
+                        score: player.score,
+                        date: leaderboard::utc_date(miniquad::date::now()),
+                        duration: field.tick as f32 * TICK,
+                        seed: field.seed,
+                        ship: player.slot,
+                    };
+                    if let Some(board) = run_mode.online_board() {
+                        online.submit(
+                            Submission {
+                                board,
+                                entry: entry.clone(),
+                                replay: replay.to_text(),
+                            },
+                            &mut *storages.data,
+                        );
+                    }
+                    shown_board = run_mode.board().unwrap_or(ENDLESS_BOARD.to_string());
+                    if let Some(rank) = save.board(&shown_board).insert(entry) {
+                        leaderboard_highlight = Some(rank);
+                        write_save(&save, &mut *storages.data, &mut save_status);
+                    }
+                    pending_names.remove(0);
+                    if pending_names.is_empty() {
+                        game_state = GameState::Leaderboard;
+                    }
+                }
+
+                let font = Some(&resources.font);
+                let lines = [
+                    (
+                        format!(
+                            "{}SCORE {}",
+                            player.label(field.players.len()),
+                            player.score
+                        ),
+                        25,
+                    ),
+                    ("ENTER YOUR NAME".to_string(), 40),
+                    // Blinking cursor while there is room for more
+                    (
+                        if name_input.len() < MAX_NAME_LENGTH && get_time() % 1.0 < 0.5 {
+                            format!("{name_input}_")
+                        } else {
+                            format!("{name_input} ")
+                        },
+                        40,
+                    ),
+                    ("ENTER TO CONFIRM".to_string(), 16),
+                ];
+                for (i, (line, font_size)) in lines.iter().enumerate() {
+                    let dimensions = measure_text(line, font, *font_size, 1.0);
+                    draw_text_ex(
+                        line,
+                        screen_width() / 2.0 - dimensions.width / 2.0,
+                        screen_height() / 2.0 - 90.0 + i as f32 * 70.0,
+                        TextParams {
+                            font,
+                            font_size: *font_size,
+                            color: player.color(),
+                            ..Default::default()
+                        },
+                    );
+                }
+            }
+            GameState::Leaderboard => {
+                if config.bindings.pressed(Action::Confirm, &gamepads)
+                    || config.bindings.pressed(Action::Back, &gamepads)
+                    || touch.tapped()
+                {
+                    game_state = GameState::MainMenu;
+                }
+                leaderboard::draw(
+                    &shown_board,
+                    save.board(&shown_board),
+                    &resources.font,
+                    leaderboard_highlight,
+                );
             }
+            GameState::OnlineScores => {
+                let pages = match online.scores() {
+                    Scores::Loaded(entries) => entries.len().div_ceil(leaderboard::MAX_ENTRIES),
+                    _ => 1,
+                };
+                let confirm =
+                    config
+                        .bindings
+                        .navigate_menu(&mut online_page, pages.max(1), &gamepads);
+                if confirm || config.bindings.pressed(Action::Back, &gamepads) || touch.tapped() {
+                    game_state = GameState::Boards;
+                }
+                let mut lines = vec![];
+                match online.scores() {
+                    Scores::Loaded(entries) => {
+                        leaderboard::draw_entries(
+                            &format!("ONLINE {}", leaderboard::name(&shown_board).to_uppercase()),
+                            entries,
+                            online_page * leaderboard::MAX_ENTRIES,
+                            &resources.font,
+                            None,
+                        );
+                        if pages > 1 {
+                            lines.push(format!("PAGE {} OF {pages}", online_page + 1));
+                        }
+                    }
+                    Scores::Loading => lines.push("LOADING...".to_string()),
+                    Scores::Failed(err) => {
+                        lines.push("SERVER UNREACHABLE".to_string());
+                        lines.push(err.to_uppercase());
+                    }
+                    Scores::Off => {
+                        lines.push("ONLINE SCORES ARE OFF".to_string());
+                        lines.push("SET SERVER UNDER [ONLINE] IN CONFIG.INI".to_string());
+                    }
+                }
+                if online.pending() > 0 {
+                    lines.push(format!("{} RUNS WAITING TO BE SENT", online.pending()));
+                }
+                for (i, line) in lines.iter().enumerate() {
+                    let dimensions = measure_text(line, Some(&resources.font), 14, 1.0);
+                    draw_text_ex(
+                        line,
+                        screen_width() / 2.0 - dimensions.width / 2.0,
+                        screen_height() - 80.0 + i as f32 * 24.0,
+                        TextParams {
+                            font: Some(&resources.font),
+                            font_size: 14,
+                            color: GRAY,
+                            ..Default::default()
+                        },
+                    );
+                }
+            }
+            GameState::Records => {
+                let mut chosen = None;
+                if config.bindings.pressed(Action::Back, &gamepads) {
+                    chosen = Some(RECORDS_MENU.len() - 1);
+                }
+                let confirm = config.bindings.navigate_menu(
+                    &mut menu_selection,
+                    RECORDS_MENU.len(),
+                    &gamepads,
+                );
+                chosen = chosen.or(list_menu(
+                    hash!(),
+                    "Records",
+                    &RECORDS_MENU,
+                    400.0,
+                    menu_selection,
+                    confirm,
+                    &resources.shop_skin,
+                ));
+                match chosen {
+                    Some(choice @ (0 | 1)) => {
+                        boards_online = choice == 1;
+                        menu_selection = 0;
+                        game_state = GameState::Boards;
+                    }
+                    Some(2) => game_state = GameState::Achievements,
+                    Some(3) => game_state = GameState::LifetimeStats,
+                    Some(_) => {
+                        menu_selection = 3;
+                        game_state = GameState::MainMenu;
+                    }
+                    None => {}
+                }
+            }
+            GameState::Boards => {
+                let entries: Vec<&str> = leaderboard::BOARDS
+                    .iter()
+                    .map(|board| leaderboard::name(board))
+                    .chain(["Back"])
+                    .collect();
+                let mut chosen = None;
+                if config.bindings.pressed(Action::Back, &gamepads) {
+                    chosen = Some(entries.len() - 1);
+                }
+                let confirm =
+                    config
+                        .bindings
+                        .navigate_menu(&mut menu_selection, entries.len(), &gamepads);
+                chosen = chosen.or(list_menu(
+                    hash!(),
+                    if boards_online {
+                        "Online"
+                    } else {
+                        "High Scores"
+                    },
+                    &entries,
+                    400.0,
+                    menu_selection,
+                    confirm,
+                    &resources.shop_skin,
+                ));
+                match chosen {
+                    Some(board) if board < leaderboard::BOARDS.len() => {
+                        shown_board = match leaderboard::BOARDS[board] {
+                            DAILY_BOARD => daily::board(daily::day(miniquad::date::now())),
+                            board => board.to_string(),
+                        };
+                        if boards_online {
+                            online.fetch(&shown_board);
+                            online_page = 0;
+                            game_state = GameState::OnlineScores;
+                        } else {
+                            leaderboard_highlight = None;
+                            game_state = GameState::Leaderboard;
+                        }
+                    }
+                    Some(_) => {
+                        menu_selection = usize::from(boards_online);
+                        game_state = GameState::Records;
+                    }
+                    None => {}
+                }
+            }
+            GameState::Achievements => {
+                if config.bindings.pressed(Action::Confirm, &gamepads)
+                    || config.bindings.pressed(Action::Back, &gamepads)
+                    || touch.tapped()
+                {
+                    game_state = GameState::Records;
+                }
+                achievements::draw(&save, &resources.font);
+            }
+            GameState::LifetimeStats => {
+                if config.bindings.pressed(Action::Confirm, &gamepads)
+                    || config.bindings.pressed(Action::Back, &gamepads)
+                    || touch.tapped()
+                {
+                    game_state = GameState::Records;
+                }
+                stats::draw_lifetime(&save, &resources.font);
+            }
+            GameState::Versus => {
+                let half = vec2(screen_width() / 2.0, screen_height());
+                let inputs = [0, 1].map(|slot| config.bindings.input(slot, &gamepads));
+
+                if versus.winner().is_none() {
+                    versus.resize(half);
+                    for events in &mut versus_events {
+                        events.clear();
+                    }
+                    accumulator = (accumulator + get_frame_time()).min(0.25);
+                    while accumulator >= TICK && versus.winner().is_none() {
+                        accumulator -= TICK;
+                        versus.step(&inputs, &mut versus_events);
+                    }
+                } else if config.bindings.pressed(Action::Confirm, &gamepads) || touch.tapped() {
+                    game_state = GameState::MainMenu;
+                }
+                if config.bindings.pressed(Action::Pause, &gamepads) {
+                    game_state = GameState::MainMenu;
+                }
+
+                for side in 0..2 {
+                    let origin = vec2(side as f32 * half.x, 0.0);
+                    let field = &versus.fields[side];
+                    let view = &mut versus_views[side];
+                    view.handle(&versus_events[side], &resources, &config.settings);
+                    view.update(field);
+                    view.draw(field, origin, &resources);
+                    view.draw_scores(field, origin);
+
+                    let player = &field.players[0];
+                    let title = format!("P{}", player.slot + 1);
+                    let title_dimensions = measure_text(title.as_str(), None, 25, 1.0);
+                    draw_text(
+                        title.as_str(),
+                        origin.x + half.x / 2.0 - title_dimensions.width / 2.0,
+                        35.0,
+                        25.0,
+                        player.color(),
+                    );
+                    if field.incoming_garbage > 0 {
+                        draw_text(
+                            format!("Incoming {}", field.incoming_garbage).as_str(),
+                            origin.x + 10.0,
+                            half.y - 15.0,
+                            25.0,
+                            RED,
+                        );
+                    }
+                }
+                draw_line(half.x, 0.0, half.x, half.y, 2.0, GRAY);
+
+                if let Some(winner) = versus.winner() {
+                    let text = format!("P{} WINS!", winner + 1);
+                    let text_dimensions = measure_text(text.as_str(), None, 50, 1.0);
+                    draw_text(
+                        text.as_str(),
+                        screen_width() / 2.0 - text_dimensions.width / 2.0,
+                        screen_height() / 2.0,
+                        50.0,
+                        versus.fields[winner].players[0].color(),
+                    );
+                }
+            }
+            GameState::Online => {
+                if config.bindings.pressed(Action::Pause, &gamepads) || touch.pause_tapped() {
+                    net_game = None;
+                }
+                let Some(net) = &mut net_game else {
+                    game_state = GameState::MainMenu;
+                    next_frame().await;
+                    continue;
+                };
+
+                let origin = vec2(
+                    (screen_width() - ARENA_SIZE.x) / 2.0,
+                    (screen_height() - ARENA_SIZE.y) / 2.0,
+                );
+                // The local player always uses the first player's controls
+                let ship = net.session.as_ref().and_then(|session| {
+                    let player = session.field.players.get(session.local_slot())?;
+                    Some((
+                        origin + vec2(player.ship.x, player.ship.y),
+                        player.ship.speed,
+                    ))
+                });
+                let input = config
+                    .bindings
+                    .input(0, &gamepads)
+                    .merge(touch.input(ship))
+                    .merge(mouse::input(config.bindings.scheme, ship));
+                events.clear();
+                accumulator = (accumulator + get_frame_time()).min(0.25);
+                while accumulator >= TICK {
+                    accumulator -= TICK;
+                    events.extend(net.update(input));
+                }
+
+                if let Some(session) = &net.session {
+                    view.handle(&events, &resources, &config.settings);
+                    view.update(&session.field);
+                    view.draw(&session.field, origin, &resources);
+                    view.draw_scores(&session.field, origin);
+                    draw_rectangle_lines(origin.x, origin.y, ARENA_SIZE.x, ARENA_SIZE.y, 2.0, GRAY);
+
+                    let status = format!(
+                        "You are P{}  Tick {}  Predicted {}",
+                        session.local_slot() + 1,
+                        session.field.tick,
+                        session.prediction()
+                    );
+                    draw_text(
+                        status.as_str(),
+                        origin.x + 10.0,
+                        origin.y + ARENA_SIZE.y - 15.0,
+                        25.0,
+                        GRAY,
+                    );
+                    if let Some(tick) = session.desync {
+                        draw_text(
+                            format!("DESYNC AT TICK {tick}").as_str(),
+                            origin.x + 10.0,
+                            origin.y + ARENA_SIZE.y - 40.0,
+                            25.0,
+                            RED,
+                        );
+                    }
+                    if session.field.game_over() {
+                        let text = "GAME OVER!";
+                        let text_dimensions = measure_text(text, None, 50, 1.0);
+                        draw_text(
+                            text,
+                            screen_width() / 2.0 - text_dimensions.width / 2.0,
+                            screen_height() / 2.0,
+                            50.0,
+                            RED,
+                        );
+                    }
+                    touch.draw();
+                } else {
+                    let text = net.status();
+                    let text_dimensions = measure_text(text, None, 40, 1.0);
+                    draw_text(
+                        text,
+                        screen_width() / 2.0 - text_dimensions.width / 2.0,
+                        screen_height() / 2.0,
+                        40.0,
+                        WHITE,
+                    );
+                }
+            }
+        }
+        if let Some(mode) = start {
+            let size = vec2(screen_width(), screen_height());
+            let seed = match mode {
+                RunMode::Daily { day, .. } => daily::seed(day),
+                RunMode::Endless
+                | RunMode::Campaign { .. }
+                | RunMode::TimeAttack { .. }
+                | RunMode::Pacifist
+                | RunMode::Practice
+                | RunMode::Tutorial => miniquad::date::now() as u64,
+            };
+            field = match mode {
+                RunMode::Campaign { level } => {
+                    Playfield::with_level(seed, size, resources.levels[level].clone())
+                }
+                RunMode::TimeAttack { limit } => {
+                    Playfield::with_time_limit(seed, size, time_attack::seconds(limit))
+                }
+                RunMode::Pacifist => Playfield::pacifist(seed, size),
+                RunMode::Tutorial => {
+                    tutorial = Tutorial::new();
+                    Tutorial::playfield(seed, size)
+                }
+                RunMode::Practice => {
+                    let mut field = Playfield::practice(seed, size);
+                    field.invincible = practice_invincible;
+                    field
+                }
+                RunMode::Endless | RunMode::Daily { .. } => Playfield::new(seed, size),
+            };
+            replay = Replay::new(field.seed, field.size);
+            field.join();
+            replay.join();
+            view.clear();
+            run_tracker = RunTracker::new();
+            accumulator = 0.0;
+            purchases = Purchases::new(&resources.shop_items);
+            run_mode = mode;
+            game_state = GameState::Playing;
         }
+        // Unlocks stay on screen through the game over and shop screens
+        achievements.draw_toasts(&resources.font);
 
+        frame_limiter.wait(config.settings.fps_cap);
         next_frame().await
     }
 }
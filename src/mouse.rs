@@ -0,0 +1,30 @@
+//! Mouse-follow controls for the first player: the ship eases towards the
+//! cursor and the mouse buttons fire and bomb.
+
+use macroquad::prelude::*;
+
+use crate::bindings::ControlScheme;
+use crate::input::{Button, PlayerInput};
+
+/// How far from the cursor the ship starts slowing down, in seconds of
+/// travel.
+const EASING: f32 = 0.15;
+
+/// Input for the first ship under `scheme`, given its position on screen
+/// and speed. Empty for the keyboard scheme.
+pub fn input(scheme: ControlScheme, ship: Option<(Vec2, f32)>) -> PlayerInput {
+    let mut input = PlayerInput::default();
+    if scheme == ControlScheme::Keyboard {
+        return input;
+    }
+    if let Some((ship, speed)) = ship {
+        input.steer(Vec2::from(mouse_position()) - ship, speed * EASING);
+    }
+    input.set(
+        Button::Fire,
+        scheme == ControlScheme::MouseAutofire || is_mouse_button_down(MouseButton::Left),
+    );
+    input.set(Button::Bomb, is_mouse_button_down(MouseButton::Right));
+    input.set(Button::Dash, is_mouse_button_down(MouseButton::Middle));
+    input
+}
@@ -0,0 +1,718 @@
+//! Peer-to-peer rollback netcode for online two-player.
+//!
+//! Each peer simulates every tick straight away, predicting the remote
+//! player's input by repeating the last one it received. When the real
+//! input turns up and differs, the session rewinds to the snapshot taken
+//! before that tick and re-simulates up to the present. Local inputs are
+//! resent until acknowledged, so a lost packet only costs latency.
+//!
+//! Peers also exchange checksums of confirmed states every
+//! `CHECKSUM_INTERVAL` ticks; a mismatch means the simulations diverged.
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::rc::Rc;
+
+use macroquad::prelude::*;
+
+use crate::input::{Button, PlayerInput};
+use crate::playfield::{Event, Playfield};
+use crate::rng::Rng;
+
+/// Online matches use a fixed arena so both peers simulate the same bounds
+/// whatever their window size.
+pub const ARENA_SIZE: Vec2 = Vec2::new(800.0, 600.0);
+/// How far the simulation may run ahead of the last confirmed tick before
+/// it stalls waiting for the peer.
+const MAX_PREDICTION: u64 = 12;
+const CHECKSUM_INTERVAL: u64 = 30;
+const MAX_INPUTS_PER_PACKET: u64 = 64;
+
+const PACKET_HELLO: u8 = b'H';
+const PACKET_WELCOME: u8 = b'W';
+const PACKET_INPUT: u8 = b'I';
+
+pub trait Transport {
+    /// Sends one datagram. Delivery is not guaranteed.
+    fn send(&mut self, packet: &[u8]);
+    /// Returns the next datagram that has arrived, if any.
+    fn recv(&mut self) -> Option<Vec<u8>>;
+}
+
+pub struct UdpTransport {
+    socket: UdpSocket,
+    peer: Option<SocketAddr>,
+}
+
+impl UdpTransport {
+    /// Listens on `port`; the peer address is learned from the first packet.
+    pub fn host(port: u16) -> io::Result<UdpTransport> {
+        let socket = UdpSocket::bind(("0.0.0.0", port))?;
+        socket.set_nonblocking(true)?;
+        Ok(UdpTransport { socket, peer: None })
+    }
+
+    pub fn connect(address: &str) -> io::Result<UdpTransport> {
+        let peer = address
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no address for peer"))?;
+        let socket = UdpSocket::bind(("0.0.0.0", 0))?;
+        socket.set_nonblocking(true)?;
+        Ok(UdpTransport {
+            socket,
+            peer: Some(peer),
+        })
+    }
+}
+
+impl Transport for UdpTransport {
+    fn send(&mut self, packet: &[u8]) {
+        if let Some(peer) = self.peer {
+            self.socket.send_to(packet, peer).ok();
+        }
+    }
+
+    fn recv(&mut self) -> Option<Vec<u8>> {
+        let mut buffer = [0; 1024];
+        loop {
+            let (len, from) = self.socket.recv_from(&mut buffer).ok()?;
+            match self.peer {
+                None => self.peer = Some(from),
+                Some(peer) if peer != from => continue,
+                Some(_) => {}
+            }
+            return Some(buffer[..len].to_vec());
+        }
+    }
+}
+
+type Wire = Rc<RefCell<VecDeque<(f64, Vec<u8>)>>>;
+
+/// In-process transport for testing on one machine. Packets are delayed by
+/// `latency` seconds and dropped with probability `loss`.
+pub struct LoopbackTransport {
+    outgoing: Wire,
+    incoming: Wire,
+    latency: f64,
+    loss: f32,
+    rng: Rng,
+}
+
+/// Two connected loopback ends.
+pub fn loopback_pair(latency: f64, loss: f32) -> (LoopbackTransport, LoopbackTransport) {
+    let a: Wire = Default::default();
+    let b: Wire = Default::default();
+    (
+        LoopbackTransport {
+            outgoing: a.clone(),
+            incoming: b.clone(),
+            latency,
+            loss,
+            rng: Rng::new(1),
+        },
+        LoopbackTransport {
+            outgoing: b,
+            incoming: a,
+            latency,
+            loss,
+            rng: Rng::new(2),
+        },
+    )
+}
+
+impl Transport for LoopbackTransport {
+    fn send(&mut self, packet: &[u8]) {
+        if self.rng.range_f32(0.0, 1.0) < self.loss {
+            return;
+        }
+        let deliver_at = miniquad::date::now() + self.latency;
+        self.outgoing
+            .borrow_mut()
+            .push_back((deliver_at, packet.to_vec()));
+    }
+
+    fn recv(&mut self) -> Option<Vec<u8>> {
+        let mut incoming = self.incoming.borrow_mut();
+        match incoming.front() {
+            Some((deliver_at, _)) if *deliver_at <= miniquad::date::now() => {
+                incoming.pop_front().map(|(_, packet)| packet)
+            }
+            _ => None,
+        }
+    }
+}
+
+pub struct RollbackSession {
+    pub field: Playfield,
+    local_slot: usize,
+    input_delay: u64,
+    /// Local inputs by tick, already shifted by the input delay.
+    local_inputs: Vec<PlayerInput>,
+    remote_inputs: Vec<Option<PlayerInput>>,
+    /// Remote input each simulated tick actually used, real or predicted.
+    used_remote: Vec<PlayerInput>,
+    /// States before each of the last few ticks, oldest first.
+    snapshots: VecDeque<Playfield>,
+    /// Ticks below this have inputs from both peers.
+    confirmed: u64,
+    /// How many of our inputs the peer has acknowledged.
+    remote_acked: u64,
+    next_checksum: u64,
+    local_checksums: VecDeque<(u64, u64)>,
+    remote_checksums: VecDeque<(u64, u64)>,
+    /// First tick at which the peers' checksums disagreed.
+    pub desync: Option<u64>,
+}
+
+impl RollbackSession {
+    pub fn new(seed: u64, local_slot: usize, input_delay: u64) -> RollbackSession {
+        let mut field = Playfield::new(seed, ARENA_SIZE);
+        field.join();
+        field.join();
+        RollbackSession {
+            field,
+            local_slot,
+            input_delay,
+            local_inputs: vec![PlayerInput::default(); input_delay as usize],
+            remote_inputs: vec![],
+            used_remote: vec![],
+            snapshots: VecDeque::new(),
+            confirmed: 0,
+            remote_acked: 0,
+            next_checksum: CHECKSUM_INTERVAL,
+            local_checksums: VecDeque::new(),
+            remote_checksums: VecDeque::new(),
+            desync: None,
+        }
+    }
+
+    pub fn local_slot(&self) -> usize {
+        self.local_slot
+    }
+
+    pub fn heard_from_peer(&self) -> bool {
+        !self.remote_inputs.is_empty()
+    }
+
+    /// Ticks simulated ahead of the last confirmed one.
+    pub fn prediction(&self) -> u64 {
+        self.field.tick - self.confirmed
+    }
+
+    /// Runs one tick with `local` as this peer's input. Returns the events of
+    /// the new tick; events from re-simulated ticks are dropped since their
+    /// effects were already shown. Stalls without simulating when too far
+    /// ahead of the peer. A correction older than the kept snapshots can't
+    /// be rolled back and is reported as a desync.
+    pub fn advance(&mut self, local: PlayerInput, transport: &mut dyn Transport) -> Vec<Event> {
+        let rollback_from = self.receive(transport);
+
+        if let Some(from) = rollback_from {
+            let now = self.field.tick;
+            match self
+                .snapshots
+                .iter()
+                .position(|snapshot| snapshot.tick == from)
+            {
+                Some(index) => {
+                    self.field = self.snapshots[index].clone();
+                    self.snapshots.truncate(index);
+                    let mut discarded = vec![];
+                    while self.field.tick < now {
+                        self.simulate_tick(&mut discarded);
+                    }
+                }
+                None => {
+                    self.desync.get_or_insert(from);
+                }
+            }
+        }
+
+        let mut events = vec![];
+        if self.prediction() < MAX_PREDICTION {
+            self.local_inputs.push(local);
+            self.simulate_tick(&mut events);
+        }
+
+        self.update_confirmed();
+        self.send(transport);
+        events
+    }
+
+    fn simulate_tick(&mut self, events: &mut Vec<Event>) {
+        let tick = self.field.tick as usize;
+        self.snapshots.push_back(self.field.clone());
+        while self.snapshots.len() as u64 > MAX_PREDICTION + 1 {
+            self.snapshots.pop_front();
+        }
+
+        let local = self.local_inputs[tick];
+        // Predict the remote input by repeating the last one we know
+        let remote = self.remote_inputs[..tick.min(self.remote_inputs.len())]
+            .iter()
+            .rev()
+            .find_map(|input| *input);
+        let remote = self
+            .remote_inputs
+            .get(tick)
+            .copied()
+            .flatten()
+            .or(remote)
+            .unwrap_or_default();
+        if self.used_remote.len() <= tick {
+            self.used_remote.resize(tick + 1, PlayerInput::default());
+        }
+        self.used_remote[tick] = remote;
+
+        let inputs = match self.local_slot {
+            0 => [local, remote],
+            _ => [remote, local],
+        };
+        self.field.step(&inputs, events);
+        // Online matches have no shop, waves roll straight on
+        if self.field.wave_cleared {
+            self.field.start_next_wave();
+        }
+    }
+
+    /// Reads pending packets and returns the earliest tick whose prediction
+    /// turned out wrong.
+    ///
+    /// Inputs are only taken for ticks from `confirmed` up to the furthest
+    /// an honest peer can be ahead, so a stray packet can't make the input
+    /// history grow without bound. Anything past that is resent later.
+    fn receive(&mut self, transport: &mut dyn Transport) -> Option<u64> {
+        let mut rollback_from: Option<u64> = None;
+        let last_tick = self.field.tick + MAX_PREDICTION + self.input_delay;
+        while let Some(packet) = transport.recv() {
+            let Some(packet) = InputPacket::decode(&packet) else {
+                continue;
+            };
+            if packet.first > last_tick {
+                continue;
+            }
+            self.remote_acked = self
+                .remote_acked
+                .max(packet.ack.min(self.local_inputs.len() as u64));
+            for (i, input) in packet.inputs.iter().enumerate() {
+                let tick = packet.first + i as u64;
+                if tick < self.confirmed {
+                    continue;
+                }
+                if tick > last_tick {
+                    break;
+                }
+                let index = tick as usize;
+                if self.remote_inputs.len() <= index {
+                    self.remote_inputs.resize(index + 1, None);
+                }
+                if self.remote_inputs[index].is_some() {
+                    continue;
+                }
+                self.remote_inputs[index] = Some(*input);
+                if tick < self.field.tick && self.used_remote[index] != *input {
+                    rollback_from = Some(rollback_from.map_or(tick, |from| from.min(tick)));
+                }
+            }
+            if let Some(checksum) = packet.checksum {
+                self.remote_checksums.push_back(checksum);
+            }
+        }
+        rollback_from
+    }
+
+    fn update_confirmed(&mut self) {
+        while self.confirmed < self.field.tick
+            && self
+                .remote_inputs
+                .get(self.confirmed as usize)
+                .is_some_and(Option::is_some)
+        {
+            self.confirmed += 1;
+        }
+
+        // Checksum the state before `next_checksum` once every tick leading
+        // up to it is confirmed
+        while self.next_checksum <= self.confirmed {
+            let tick = self.next_checksum;
+            let state = if self.field.tick == tick {
+                Some(&self.field)
+            } else {
+                self.snapshots.iter().find(|snapshot| snapshot.tick == tick)
+            };
+            if let Some(state) = state {
+                self.local_checksums.push_back((tick, state.checksum()));
+            }
+            self.next_checksum += CHECKSUM_INTERVAL;
+        }
+
+        // Compare with whatever the peer has reported
+        self.remote_checksums.retain(|&(tick, remote)| {
+            match self
+                .local_checksums
+                .iter()
+                .find(|(local_tick, _)| *local_tick == tick)
+            {
+                Some((_, local)) => {
+                    if *local != remote && self.desync.is_none() {
+                        self.desync = Some(tick);
+                    }
+                    false
+                }
+                None => tick > self.confirmed.saturating_sub(10 * CHECKSUM_INTERVAL),
+            }
+        });
+        while self.local_checksums.len() > 16 {
+            self.local_checksums.pop_front();
+        }
+    }
+
+    fn send(&mut self, transport: &mut dyn Transport) {
+        let received = self
+            .remote_inputs
+            .iter()
+            .take_while(|input| input.is_some())
+            .count() as u64;
+        let first = self.remote_acked;
+        let last = (self.local_inputs.len() as u64).min(first + MAX_INPUTS_PER_PACKET);
+        let packet = InputPacket {
+            ack: received,
+            first,
+            inputs: self.local_inputs[first as usize..last as usize].to_vec(),
+            checksum: self.local_checksums.back().copied(),
+        };
+        transport.send(&packet.encode());
+    }
+}
+
+/// `I`, ack: u32, first: u32, count: u8, inputs, then optionally
+/// checksum tick: u32 and checksum: u64. Little endian throughout.
+struct InputPacket {
+    ack: u64,
+    first: u64,
+    inputs: Vec<PlayerInput>,
+    checksum: Option<(u64, u64)>,
+}
+
+impl InputPacket {
+    fn encode(&self) -> Vec<u8> {
+        let mut bytes = vec![PACKET_INPUT];
+        bytes.extend((self.ack as u32).to_le_bytes());
+        bytes.extend((self.first as u32).to_le_bytes());
+        bytes.push(self.inputs.len() as u8);
+        for input in &self.inputs {
+            bytes.extend(input.to_bytes());
+        }
+        if let Some((tick, checksum)) = self.checksum {
+            bytes.extend((tick as u32).to_le_bytes());
+            bytes.extend(checksum.to_le_bytes());
+        }
+        bytes
+    }
+
+    fn decode(bytes: &[u8]) -> Option<InputPacket> {
+        let (&kind, rest) = bytes.split_first()?;
+        if kind != PACKET_INPUT || rest.len() < 9 {
+            return None;
+        }
+        let ack = u32::from_le_bytes(rest[0..4].try_into().ok()?) as u64;
+        let first = u32::from_le_bytes(rest[4..8].try_into().ok()?) as u64;
+        let count = rest[8] as usize;
+        let rest = &rest[9..];
+        let inputs_len = count * PlayerInput::SIZE;
+        if rest.len() < inputs_len {
+            return None;
+        }
+        let inputs = rest[..inputs_len]
+            .chunks(PlayerInput::SIZE)
+            .map(PlayerInput::from_bytes)
+            .collect();
+        let rest = &rest[inputs_len..];
+        let checksum = if rest.len() >= 12 {
+            Some((
+                u32::from_le_bytes(rest[0..4].try_into().ok()?) as u64,
+                u64::from_le_bytes(rest[4..12].try_into().ok()?),
+            ))
+        } else {
+            None
+        };
+        Some(InputPacket {
+            ack,
+            first,
+            inputs,
+            checksum,
+        })
+    }
+}
+
+pub enum NetMode {
+    Host(u16),
+    Connect(String),
+    /// Both peers in this process, the second one played by a bot.
+    Loopback {
+        latency: f64,
+        loss: f32,
+    },
+}
+
+pub struct NetOptions {
+    pub mode: NetMode,
+    pub input_delay: u64,
+}
+
+impl NetOptions {
+    /// Reads `--host <port>`, `--connect <address>` or `--loopback`, plus
+    /// `--input-delay <ticks>`, `--latency <ms>` and `--loss <fraction>`.
+    pub fn from_args(args: impl Iterator<Item = String>) -> Option<NetOptions> {
+        let mut mode = None;
+        let mut input_delay = 2;
+        let mut latency = 0.1;
+        let mut loss = 0.0;
+        let mut args = args.peekable();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--host" => {
+                    let port = args.next()?.parse().ok()?;
+                    mode = Some(NetMode::Host(port));
+                }
+                "--connect" => mode = Some(NetMode::Connect(args.next()?)),
+                "--loopback" => mode = Some(NetMode::Loopback { latency, loss }),
+                "--input-delay" => input_delay = args.next()?.parse().ok()?,
+                "--latency" => latency = args.next()?.parse::<f64>().ok()? / 1000.0,
+                "--loss" => loss = args.next()?.parse().ok()?,
+                _ => {}
+            }
+        }
+        // Latency and loss may come after --loopback
+        if let Some(NetMode::Loopback { .. }) = mode {
+            mode = Some(NetMode::Loopback { latency, loss });
+        }
+        Some(NetOptions {
+            mode: mode?,
+            input_delay,
+        })
+    }
+}
+
+/// Opponent simulated in-process for `--loopback`, mashing random buttons.
+struct LoopbackPeer {
+    session: RollbackSession,
+    transport: LoopbackTransport,
+    rng: Rng,
+    input: PlayerInput,
+}
+
+impl LoopbackPeer {
+    fn update(&mut self) {
+        if self.rng.range_u32(0, 20) == 0 {
+            self.input = PlayerInput::default();
+            for button in [
+                Button::Left,
+                Button::Right,
+                Button::Up,
+                Button::Down,
+                Button::Fire,
+            ] {
+                self.input.set(button, self.rng.range_u32(0, 3) == 0);
+            }
+        }
+        self.session.advance(self.input, &mut self.transport);
+    }
+}
+
+/// An online match from handshake to the end of play.
+pub struct NetGame {
+    transport: Box<dyn Transport>,
+    hosting: bool,
+    seed: u64,
+    input_delay: u64,
+    pub session: Option<RollbackSession>,
+    peer: Option<LoopbackPeer>,
+}
+
+impl NetGame {
+    pub fn start(options: NetOptions) -> io::Result<NetGame> {
+        let seed = miniquad::date::now().to_bits();
+        let game = match options.mode {
+            NetMode::Host(port) => NetGame {
+                transport: Box::new(UdpTransport::host(port)?),
+                hosting: true,
+                seed,
+                input_delay: options.input_delay,
+                session: None,
+                peer: None,
+            },
+            NetMode::Connect(address) => NetGame {
+                transport: Box::new(UdpTransport::connect(&address)?),
+                hosting: false,
+                seed,
+                input_delay: options.input_delay,
+                session: None,
+                peer: None,
+            },
+            NetMode::Loopback { latency, loss } => {
+                let (local, remote) = loopback_pair(latency, loss);
+                NetGame {
+                    transport: Box::new(local),
+                    hosting: true,
+                    seed,
+                    input_delay: options.input_delay,
+                    session: Some(RollbackSession::new(seed, 0, options.input_delay)),
+                    peer: Some(LoopbackPeer {
+                        session: RollbackSession::new(seed, 1, options.input_delay),
+                        transport: remote,
+                        rng: Rng::new(seed ^ 0xb07),
+                        input: PlayerInput::default(),
+                    }),
+                }
+            }
+        };
+        Ok(game)
+    }
+
+    /// Advances one tick, or keeps handshaking until the peer is found.
+    pub fn update(&mut self, local: PlayerInput) -> Vec<Event> {
+        let Some(session) = &mut self.session else {
+            self.handshake();
+            return vec![];
+        };
+        // Repeat the welcome until the client shows it got it
+        if self.hosting && self.peer.is_none() && !session.heard_from_peer() {
+            self.transport.send(&welcome(self.seed));
+        }
+        let events = session.advance(local, self.transport.as_mut());
+        if let Some(peer) = &mut self.peer {
+            peer.update();
+        }
+        events
+    }
+
+    fn handshake(&mut self) {
+        while let Some(packet) = self.transport.recv() {
+            match packet.first() {
+                Some(&PACKET_HELLO) if self.hosting => {
+                    self.transport.send(&welcome(self.seed));
+                    self.session = Some(RollbackSession::new(self.seed, 0, self.input_delay));
+                    return;
+                }
+                Some(&PACKET_WELCOME) if !self.hosting && packet.len() == 9 => {
+                    let seed = u64::from_le_bytes(packet[1..9].try_into().unwrap());
+                    self.session = Some(RollbackSession::new(seed, 1, self.input_delay));
+                    return;
+                }
+                _ => {}
+            }
+        }
+        // Keep knocking in case the hello was lost
+        if !self.hosting {
+            self.transport.send(&[PACKET_HELLO]);
+        }
+    }
+
+    pub fn status(&self) -> &'static str {
+        match (&self.session, self.hosting) {
+            (Some(_), _) => "Connected",
+            (None, true) => "Waiting for a player to connect",
+            (None, false) => "Connecting",
+        }
+    }
+}
+
+fn welcome(seed: u64) -> Vec<u8> {
+    let mut welcome = vec![PACKET_WELCOME];
+    welcome.extend(seed.to_le_bytes());
+    welcome
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs both peers of a loopback match for `ticks` ticks, each mashing
+    /// its own random buttons, with `meddle` called on them after every
+    /// tick.
+    fn play(
+        latency: f64,
+        loss: f32,
+        ticks: u64,
+        mut meddle: impl FnMut(u64, &mut [RollbackSession; 2]),
+    ) -> [RollbackSession; 2] {
+        let (transport_a, transport_b) = loopback_pair(latency, loss);
+        let mut transports = [transport_a, transport_b];
+        let mut sessions = [RollbackSession::new(7, 0, 2), RollbackSession::new(7, 1, 2)];
+        let mut rngs = [Rng::new(11), Rng::new(12)];
+        let mut inputs = [PlayerInput::default(); 2];
+        for tick in 0..ticks {
+            for side in 0..2 {
+                if rngs[side].range_u32(0, 10) == 0 {
+                    for button in [Button::Left, Button::Right, Button::Up, Button::Fire] {
+                        inputs[side].set(button, rngs[side].range_u32(0, 2) == 0);
+                    }
+                }
+                sessions[side].advance(inputs[side], &mut transports[side]);
+            }
+            meddle(tick, &mut sessions);
+            // Real time passes so the latency elapses, a few ticks' worth
+            // at a time
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+        sessions
+    }
+
+    #[test]
+    fn peers_converge_through_latency_and_loss() {
+        let sessions = play(0.004, 0.2, 600, |_, _| {});
+        for session in &sessions {
+            assert_eq!(session.desync, None);
+            assert!(session.confirmed > 300, "stalled at {}", session.confirmed);
+        }
+        let [a, b] = &sessions;
+        let compared: Vec<_> = a
+            .local_checksums
+            .iter()
+            .filter_map(|(tick, checksum)| {
+                let (_, other) = b.local_checksums.iter().find(|(t, _)| t == tick)?;
+                Some((checksum, other))
+            })
+            .collect();
+        assert!(!compared.is_empty());
+        for (checksum, other) in compared {
+            assert_eq!(checksum, other);
+        }
+    }
+
+    #[test]
+    fn divergence_is_flagged() {
+        let sessions = play(0.004, 0.1, 600, |tick, sessions| {
+            // Snapshots too, so a rollback can't undo it
+            if tick == 200 {
+                let session = &mut sessions[1];
+                for field in session.snapshots.iter_mut().chain([&mut session.field]) {
+                    field.players[0].score += 1;
+                }
+            }
+        });
+        for session in &sessions {
+            assert!(session.desync.is_some());
+        }
+    }
+
+    #[test]
+    fn far_future_packets_are_ignored() {
+        let (mut attacker, mut transport) = loopback_pair(0.0, 0.0);
+        let mut session = RollbackSession::new(7, 0, 2);
+        let packet = InputPacket {
+            ack: u32::MAX as u64,
+            first: u32::MAX as u64 - 1,
+            inputs: vec![PlayerInput::default(); 2],
+            checksum: None,
+        };
+        attacker.send(&packet.encode());
+        session.advance(PlayerInput::default(), &mut transport);
+        assert!(session.remote_inputs.is_empty());
+        assert_eq!(session.desync, None);
+    }
+}
@@ -0,0 +1,369 @@
+//! Optional online leaderboard. Scores go to the server set under
+//! `[online]` in the config, with their seed and replay, and the top 100 of
+//! a board can be fetched from it. All traffic happens on a background
+//! thread so a slow or missing server never holds up the game. Submissions
+//! that didn't get through are kept in the data storage and retried with a
+//! growing delay, across restarts too; ones the server turns down are
+//! dropped.
+//!
+//! The protocol is plain HTTP with INI bodies, see
+//! `src/bin/leaderboard_server.rs` for a server that speaks it:
+//!
+//! - `GET /boards/<board>?limit=<n>` returns the best `n` runs as `[entry]`
+//!   sections, best first
+//! - `POST /boards/<board>` with an `[entry]` section including a `replay`
+//!   key submits a run
+
+use std::fmt;
+use std::sync::mpsc::{Receiver, Sender};
+
+use macroquad::prelude::*;
+
+use crate::ini::{self, Section};
+use crate::leaderboard::Entry;
+use crate::persist::Storage;
+
+/// Runs fetched for display.
+pub const TOP: usize = 100;
+/// Submissions still to be sent, in the data storage.
+const QUEUE_FILE: &str = "online_queue.ini";
+/// Seconds to wait before the first retry, doubled after every failure.
+const RETRY_DELAY: f64 = 5.0;
+const MAX_RETRY_DELAY: f64 = 300.0;
+
+/// A finished run on its way to the server.
+#[derive(Clone, Debug)]
+pub struct Submission {
+    /// A board like `leaderboard::ENDLESS_BOARD`, or today's daily board
+    /// from `daily::board`.
+    pub board: String,
+    pub entry: Entry,
+    /// `Replay::to_text` of the run.
+    pub replay: String,
+}
+
+impl Submission {
+    fn from_section(section: &Section) -> Option<Submission> {
+        Some(Submission {
+            board: section.get("board")?.to_string(),
+            entry: Entry::from_section(section)?,
+            replay: section.get("replay")?.to_string(),
+        })
+    }
+
+    /// An `[entry]` section, as sent to the server and kept in the queue.
+    fn to_ini(&self) -> String {
+        format!(
+            "[entry]\nboard = {}\n{}replay = {}\n",
+            self.board,
+            self.entry.to_ini(),
+            self.replay
+        )
+    }
+}
+
+/// What is known of the board last asked for with `fetch`.
+pub enum Scores {
+    /// No server is set.
+    Off,
+    Loading,
+    Failed(String),
+    Loaded(Vec<Entry>),
+}
+
+enum Request {
+    Fetch(String),
+    Submit(Submission),
+}
+
+enum Response {
+    Fetched(Result<Vec<Entry>, String>),
+    Submitted(Result<(), RequestError>),
+}
+
+/// Why a request didn't go through.
+enum RequestError {
+    /// The server answered with a 4xx status, so sending the same request
+    /// again won't change its mind.
+    Rejected(String),
+    /// The server couldn't be reached or had trouble of its own.
+    Failed(String),
+}
+
+impl From<String> for RequestError {
+    fn from(message: String) -> RequestError {
+        RequestError::Failed(message)
+    }
+}
+
+impl fmt::Display for RequestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RequestError::Rejected(message) | RequestError::Failed(message) => f.write_str(message),
+        }
+    }
+}
+
+pub struct OnlineLeaderboard {
+    /// `None` when online scores are off.
+    worker: Option<(Sender<Request>, Receiver<Response>)>,
+    queue: Vec<Submission>,
+    /// Whether the first submission in the queue is with the worker.
+    sending: bool,
+    retry_delay: f64,
+    /// `get_time` at which the queue may be sent again.
+    retry_at: f64,
+    scores: Scores,
+}
+
+impl OnlineLeaderboard {
+    /// Talks to `server`, an `http://host:port` URL, or to nothing if it is
+    /// empty. Submissions left over from earlier sessions are loaded from
+    /// `storage`; a queue that can't be read is reported and dropped.
+    pub fn new(server: &str, storage: &dyn Storage) -> (OnlineLeaderboard, Option<String>) {
+        let (queue, mut message) = match storage.read(QUEUE_FILE) {
+            Ok(text) => (
+                ini::parse(&text.unwrap_or_default())
+                    .iter()
+                    .filter(|section| section.name == "entry")
+                    .filter_map(Submission::from_section)
+                    .collect(),
+                None,
+            ),
+            Err(err) => (vec![], Some(format!("Could not read {QUEUE_FILE}: {err}"))),
+        };
+        let worker = if server.is_empty() {
+            None
+        } else {
+            let worker = spawn_worker(server);
+            if worker.is_none() {
+                message = message.or(Some(
+                    "Online scores are not available on this platform".to_string(),
+                ));
+            }
+            worker
+        };
+        let online = OnlineLeaderboard {
+            worker,
+            queue,
+            sending: false,
+            retry_delay: RETRY_DELAY,
+            retry_at: 0.0,
+            scores: Scores::Off,
+        };
+        (online, message)
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.worker.is_some()
+    }
+
+    /// Submissions not yet taken by the server.
+    pub fn pending(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn scores(&self) -> &Scores {
+        &self.scores
+    }
+
+    /// Starts loading the top of `board`.
+    pub fn fetch(&mut self, board: &str) {
+        self.scores = match &self.worker {
+            Some((requests, _)) => match requests.send(Request::Fetch(board.to_string())) {
+                Ok(()) => Scores::Loading,
+                Err(_) => Scores::Failed("Online scores stopped working".to_string()),
+            },
+            None => Scores::Off,
+        };
+    }
+
+    /// Queues `submission` to be sent. The queue is written to `storage`
+    /// straight away so the run isn't lost if the game is closed before the
+    /// server takes it.
+    pub fn submit(&mut self, submission: Submission, storage: &mut dyn Storage) {
+        if !self.enabled() {
+            return;
+        }
+        self.queue.push(submission);
+        self.write_queue(storage);
+    }
+
+    /// Handles the worker's replies and sends the next submission when it
+    /// is due. Call once a frame.
+    pub fn update(&mut self, storage: &mut dyn Storage) {
+        let Some((requests, responses)) = &self.worker else {
+            return;
+        };
+        let mut sent = false;
+        for response in responses.try_iter() {
+            match response {
+                Response::Fetched(Ok(entries)) => self.scores = Scores::Loaded(entries),
+                Response::Fetched(Err(err)) => self.scores = Scores::Failed(err),
+                Response::Submitted(result) => {
+                    self.sending = false;
+                    match result {
+                        Ok(()) => {
+                            self.queue.remove(0);
+                            self.retry_delay = RETRY_DELAY;
+                            sent = true;
+                        }
+                        Err(RequestError::Rejected(err)) => {
+                            warn!("Score was rejected, dropping it: {}", err);
+                            self.queue.remove(0);
+                            self.retry_delay = RETRY_DELAY;
+                            sent = true;
+                        }
+                        Err(RequestError::Failed(err)) => {
+                            warn!("Could not submit score, retrying later: {}", err);
+                            self.retry_at = get_time() + self.retry_delay;
+                            self.retry_delay = (self.retry_delay * 2.0).min(MAX_RETRY_DELAY);
+                        }
+                    }
+                }
+            }
+        }
+
+        if !self.sending
+            && let Some(submission) = self.queue.first()
+            && get_time() >= self.retry_at
+        {
+            self.sending = requests.send(Request::Submit(submission.clone())).is_ok();
+        }
+        if sent {
+            self.write_queue(storage);
+        }
+    }
+
+    fn write_queue(&self, storage: &mut dyn Storage) {
+        let text: String = self.queue.iter().map(Submission::to_ini).collect();
+        if let Err(err) = storage.write(QUEUE_FILE, &text) {
+            error!("Could not save {}: {}", QUEUE_FILE, err);
+        }
+    }
+}
+
+/// Runs requests to `server` on a thread of their own, one at a time.
+#[cfg(not(target_arch = "wasm32"))]
+fn spawn_worker(server: &str) -> Option<(Sender<Request>, Receiver<Response>)> {
+    use std::sync::mpsc::channel;
+
+    let (requests, requests_receiver) = channel();
+    let (responses_sender, responses) = channel();
+    let server = server.to_string();
+    let worker = move || {
+        for request in requests_receiver {
+            let response = match request {
+                Request::Fetch(board) => {
+                    let path = format!("/boards/{board}?limit={TOP}");
+                    Response::Fetched(
+                        http::request(&server, "GET", &path, "")
+                            .map(|body| {
+                                let mut entries =
+                                    crate::leaderboard::parse_entries(&ini::parse(&body));
+                                entries.truncate(TOP);
+                                entries
+                            })
+                            .map_err(|err| err.to_string()),
+                    )
+                }
+                Request::Submit(submission) => {
+                    let path = format!("/boards/{}", submission.board);
+                    let body = submission.to_ini();
+                    Response::Submitted(http::request(&server, "POST", &path, &body).map(|_| ()))
+                }
+            };
+            if responses_sender.send(response).is_err() {
+                break;
+            }
+        }
+    };
+    match std::thread::Builder::new()
+        .name("online-leaderboard".to_string())
+        .spawn(worker)
+    {
+        Ok(_) => Some((requests, responses)),
+        Err(err) => {
+            error!("Could not start online scores: {}", err);
+            None
+        }
+    }
+}
+
+/// The browser build has no threads or sockets to talk to the server with.
+#[cfg(target_arch = "wasm32")]
+fn spawn_worker(_server: &str) -> Option<(Sender<Request>, Receiver<Response>)> {
+    None
+}
+
+/// Just enough HTTP/1.0 to talk to the leaderboard server.
+#[cfg(not(target_arch = "wasm32"))]
+mod http {
+    use std::io::{Read, Write};
+    use std::net::{TcpStream, ToSocketAddrs};
+    use std::time::Duration;
+
+    use super::RequestError;
+
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// Sends `method path` with `body` to `server`, an `http://host[:port]`
+    /// URL, and returns the body of a successful response.
+    pub fn request(
+        server: &str,
+        method: &str,
+        path: &str,
+        body: &str,
+    ) -> Result<String, RequestError> {
+        let address = server
+            .strip_prefix("http://")
+            .ok_or_else(|| format!("{server} is not an http:// URL"))?;
+        let host = address.split_once('/').map_or(address, |(host, _)| host);
+        let base = address[host.len()..].trim_end_matches('/');
+        let with_port = if host.contains(':') {
+            host.to_string()
+        } else {
+            format!("{host}:80")
+        };
+
+        let addresses = with_port
+            .to_socket_addrs()
+            .map_err(|err| format!("{host}: {err}"))?;
+        let mut last_error = format!("{host}: no address");
+        let mut stream = None;
+        for address in addresses {
+            match TcpStream::connect_timeout(&address, TIMEOUT) {
+                Ok(connected) => {
+                    stream = Some(connected);
+                    break;
+                }
+                Err(err) => last_error = format!("{host}: {err}"),
+            }
+        }
+        let mut stream = stream.ok_or(last_error)?;
+        let io_error = |err: std::io::Error| format!("{host}: {err}");
+        stream.set_read_timeout(Some(TIMEOUT)).map_err(io_error)?;
+        stream.set_write_timeout(Some(TIMEOUT)).map_err(io_error)?;
+
+        write!(
+            stream,
+            "{method} {base}{path} HTTP/1.0\r\nHost: {host}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\n\r\n{body}",
+            body.len()
+        )
+        .map_err(io_error)?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response).map_err(io_error)?;
+
+        let (head, body) = response
+            .split_once("\r\n\r\n")
+            .ok_or_else(|| format!("{host}: incomplete response"))?;
+        let status = head.lines().next().unwrap_or_default();
+        match status.split_whitespace().nth(1) {
+            Some(code) if code.starts_with('2') => Ok(body.to_string()),
+            Some(code) if code.starts_with('4') => {
+                Err(RequestError::Rejected(format!("{host}: {status}")))
+            }
+            _ => Err(format!("{host}: {status}").into()),
+        }
+    }
+}
@@ -0,0 +1,242 @@
+//! Named text files that outlive the game: files in the per-user
+//! directories on desktop and `localStorage` in the browser, where there is
+//! no file system. The save, the config and anything else worth keeping go
+//! through a `Storage` rather than `std::fs`.
+
+use std::collections::BTreeMap;
+use std::io;
+#[cfg(not(target_arch = "wasm32"))]
+use std::{
+    fs::{self, File},
+    io::{ErrorKind, Write},
+    path::PathBuf,
+};
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::dirs::Dirs;
+
+pub trait Storage {
+    /// Contents of `name`, or `None` if there is no such file.
+    fn read(&self, name: &str) -> io::Result<Option<String>>;
+
+    /// Replaces `name` in one go: if this fails, the old contents are left
+    /// whole.
+    fn write(&mut self, name: &str, text: &str) -> io::Result<()>;
+
+    /// Moves `from` to `to`, replacing anything there.
+    fn rename(&mut self, from: &str, to: &str) -> io::Result<()>;
+
+    /// Like `write`, but first copies the current contents to the backup
+    /// named by `backup_name`. An unreadable file isn't worth backing up.
+    fn write_with_backup(&mut self, name: &str, text: &str) -> io::Result<()> {
+        if let Ok(Some(old)) = self.read(name) {
+            self.write(&backup_name(name), &old)?;
+        }
+        self.write(name, text)
+    }
+}
+
+pub fn backup_name(name: &str) -> String {
+    format!("{name}.bak")
+}
+
+/// Everything the game stores, split by purpose.
+pub struct Storages {
+    /// Save data: scores, unlocks and stats.
+    pub data: Box<dyn Storage>,
+    /// Settings.
+    pub config: Box<dyn Storage>,
+    /// Where builds from before the save format left their files.
+    pub legacy: Box<dyn Storage>,
+}
+
+impl Storages {
+    /// Files in the directories picked by `Dirs::from_args`, with legacy
+    /// files in the current directory, where they used to be written.
+    /// `--no-save` keeps everything in memory instead, for demos and
+    /// automated runs that mustn't touch the player's files.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn open(args: impl Iterator<Item = String>) -> Storages {
+        let args: Vec<String> = args.collect();
+        if args.iter().any(|arg| arg == "--no-save") {
+            return Storages::in_memory();
+        }
+        let dirs = Dirs::from_args(args.into_iter());
+        Storages {
+            data: Box::new(FileStorage::new(dirs.data)),
+            config: Box::new(FileStorage::new(dirs.config)),
+            legacy: Box::new(FileStorage::new(PathBuf::new())),
+        }
+    }
+
+    /// `localStorage`, or memory for this session if the browser won't
+    /// allow it. The web build never saved anything before, so there are
+    /// no legacy files.
+    #[cfg(target_arch = "wasm32")]
+    pub fn open(_args: impl Iterator<Item = String>) -> Storages {
+        let local = |prefix| -> Box<dyn Storage> {
+            match LocalStorage::new(prefix) {
+                Some(storage) => Box::new(storage),
+                None => {
+                    macroquad::logging::error!("Browser storage unavailable, nothing will be kept");
+                    Box::new(MemoryStorage::default())
+                }
+            }
+        };
+        Storages {
+            data: local("my_game/data/"),
+            config: local("my_game/config/"),
+            legacy: Box::new(MemoryStorage::default()),
+        }
+    }
+
+    pub fn in_memory() -> Storages {
+        Storages {
+            data: Box::new(MemoryStorage::default()),
+            config: Box::new(MemoryStorage::default()),
+            legacy: Box::new(MemoryStorage::default()),
+        }
+    }
+}
+
+/// Files in one directory, which is created on the first write.
+#[cfg(not(target_arch = "wasm32"))]
+pub struct FileStorage {
+    dir: PathBuf,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl FileStorage {
+    pub fn new(dir: PathBuf) -> FileStorage {
+        FileStorage { dir }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Storage for FileStorage {
+    fn read(&self, name: &str) -> io::Result<Option<String>> {
+        match fs::read_to_string(self.dir.join(name)) {
+            Ok(text) => Ok(Some(text)),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Writes to a temporary file and renames it into place, so a crash
+    /// halfway leaves the old file alone.
+    fn write(&mut self, name: &str, text: &str) -> io::Result<()> {
+        if !self.dir.as_os_str().is_empty() {
+            fs::create_dir_all(&self.dir)?;
+        }
+        let temp = self.dir.join(format!("{name}.tmp"));
+        let mut file = File::create(&temp)?;
+        file.write_all(text.as_bytes())?;
+        file.sync_all()?;
+        drop(file);
+        fs::rename(temp, self.dir.join(name))
+    }
+
+    fn rename(&mut self, from: &str, to: &str) -> io::Result<()> {
+        fs::rename(self.dir.join(from), self.dir.join(to))
+    }
+}
+
+/// The browser's `localStorage`, through the `my_game_storage` plugin in
+/// `index.html`. Names are prefixed to keep different kinds of files apart.
+#[cfg(target_arch = "wasm32")]
+pub struct LocalStorage {
+    prefix: &'static str,
+}
+
+#[cfg(target_arch = "wasm32")]
+unsafe extern "C" {
+    fn my_game_storage_available() -> bool;
+    /// Length in bytes of the item, or -1 if there is none.
+    fn my_game_storage_len(key: *const u8, key_len: usize) -> i32;
+    fn my_game_storage_read(key: *const u8, key_len: usize, buffer: *mut u8, buffer_len: usize);
+    /// False if the browser refused, usually because storage is full.
+    fn my_game_storage_write(
+        key: *const u8,
+        key_len: usize,
+        value: *const u8,
+        value_len: usize,
+    ) -> bool;
+    fn my_game_storage_remove(key: *const u8, key_len: usize);
+}
+
+#[cfg(target_arch = "wasm32")]
+impl LocalStorage {
+    /// `None` if the browser blocks storage, as some do in private windows.
+    pub fn new(prefix: &'static str) -> Option<LocalStorage> {
+        unsafe { my_game_storage_available() }.then_some(LocalStorage { prefix })
+    }
+
+    fn key(&self, name: &str) -> String {
+        format!("{}{name}", self.prefix)
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl Storage for LocalStorage {
+    fn read(&self, name: &str) -> io::Result<Option<String>> {
+        let key = self.key(name);
+        let len = unsafe { my_game_storage_len(key.as_ptr(), key.len()) };
+        if len < 0 {
+            return Ok(None);
+        }
+        let mut buffer = vec![0; len as usize];
+        unsafe { my_game_storage_read(key.as_ptr(), key.len(), buffer.as_mut_ptr(), buffer.len()) };
+        String::from_utf8(buffer)
+            .map(Some)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    /// A single `setItem`, which either happens or doesn't.
+    fn write(&mut self, name: &str, text: &str) -> io::Result<()> {
+        let key = self.key(name);
+        let written =
+            unsafe { my_game_storage_write(key.as_ptr(), key.len(), text.as_ptr(), text.len()) };
+        if written {
+            Ok(())
+        } else {
+            Err(io::Error::other("browser storage is full or disabled"))
+        }
+    }
+
+    fn rename(&mut self, from: &str, to: &str) -> io::Result<()> {
+        let text = self
+            .read(from)?
+            .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))?;
+        self.write(to, &text)?;
+        let key = self.key(from);
+        unsafe { my_game_storage_remove(key.as_ptr(), key.len()) };
+        Ok(())
+    }
+}
+
+/// Files that only last as long as the value, for unit tests, `--no-save`
+/// and browsers that won't store anything.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryStorage {
+    pub files: BTreeMap<String, String>,
+}
+
+impl Storage for MemoryStorage {
+    fn read(&self, name: &str) -> io::Result<Option<String>> {
+        Ok(self.files.get(name).cloned())
+    }
+
+    fn write(&mut self, name: &str, text: &str) -> io::Result<()> {
+        self.files.insert(name.to_string(), text.to_string());
+        Ok(())
+    }
+
+    fn rename(&mut self, from: &str, to: &str) -> io::Result<()> {
+        let text = self
+            .files
+            .remove(from)
+            .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))?;
+        self.files.insert(to.to_string(), text);
+        Ok(())
+    }
+}
@@ -0,0 +1,113 @@
+use macroquad::prelude::*;
+
+use crate::Shape;
+use crate::input::PlayerInput;
+
+pub const MAX_PLAYERS: usize = 2;
+/// Seconds after a kill for the next one to extend the chain.
+pub const CHAIN_WINDOW: f32 = 2.0;
+/// Once the window runs out, the multiplier drops a step this often.
+const CHAIN_DECAY: f32 = 0.75;
+const KILLS_PER_MULTIPLIER: u32 = 4;
+const MAX_MULTIPLIER: u32 = 8;
+
+#[derive(Clone, Debug)]
+pub struct Player {
+    pub slot: usize,
+    pub ship: Shape,
+    pub score: u32,
+    pub lives: u32,
+    pub shield: u32,
+    pub bombs: u32,
+    pub invulnerable: f32,
+    pub fire_timer: f32,
+    /// Time left in the current dash.
+    pub dash: f32,
+    pub dash_cooldown: f32,
+    /// Kills in the current chain, which set the score multiplier.
+    pub chain: u32,
+    /// Time left to extend the chain before it starts decaying.
+    pub chain_timer: f32,
+    /// Input from the previous tick, for detecting presses.
+    pub input: PlayerInput,
+}
+
+impl Player {
+    pub fn new(slot: usize, position: Vec2, speed: f32) -> Player {
+        Player {
+            slot,
+            ship: Shape {
+                size: 32.0,
+                speed,
+                x: position.x,
+                y: position.y,
+                collided: false,
+                health: 0,
+            },
+            score: 0,
+            lives: 1,
+            shield: 0,
+            bombs: 0,
+            invulnerable: 0.0,
+            fire_timer: 0.0,
+            dash: 0.0,
+            dash_cooldown: 0.0,
+            chain: 0,
+            chain_timer: 0.0,
+            input: PlayerInput::default(),
+        }
+    }
+
+    pub fn alive(&self) -> bool {
+        self.lives > 0
+    }
+
+    /// Score multiplier from the chain: x1, then one more for every few
+    /// kills.
+    pub fn multiplier(&self) -> u32 {
+        (1 + self.chain / KILLS_PER_MULTIPLIER).min(MAX_MULTIPLIER)
+    }
+
+    /// Scores a kill worth `points` at the current multiplier, then extends
+    /// the chain.
+    pub fn score_kill(&mut self, points: u32) {
+        self.score += points * self.multiplier();
+        self.chain += 1;
+        self.chain_timer = CHAIN_WINDOW;
+    }
+
+    /// Runs the chain timer down, dropping the multiplier a step at a time
+    /// once it expires.
+    pub fn update_chain(&mut self, delta_time: f32) {
+        if self.chain == 0 {
+            return;
+        }
+        self.chain_timer -= delta_time;
+        if self.chain_timer <= 0.0 {
+            let step = self.chain / KILLS_PER_MULTIPLIER;
+            self.chain = step.saturating_sub(1) * KILLS_PER_MULTIPLIER;
+            self.chain_timer = CHAIN_DECAY;
+        }
+    }
+
+    pub fn break_chain(&mut self) {
+        self.chain = 0;
+        self.chain_timer = 0.0;
+    }
+
+    pub fn color(&self) -> Color {
+        match self.slot {
+            0 => WHITE,
+            _ => Color::new(1.0, 0.7, 0.5, 1.0),
+        }
+    }
+
+    /// Short prefix for HUD lines, empty when playing alone.
+    pub fn label(&self, player_count: usize) -> String {
+        if player_count > 1 {
+            format!("P{} ", self.slot + 1)
+        } else {
+            String::new()
+        }
+    }
+}
@@ -0,0 +1,779 @@
+//! Gameplay simulation for one run: ships, enemies and bullets advanced in
+//! fixed ticks. Given the same seed and inputs it always produces the same
+//! state, which rollback netcode depends on. Sprites, particles and sound
+//! live in `PlayfieldView` and react to the `Event`s emitted here.
+
+use macroquad::prelude::*;
+
+use crate::Shape;
+use crate::campaign::{BossScript, Level};
+use crate::input::{Button, PlayerInput};
+use crate::player::Player;
+use crate::rng::Rng;
+
+pub const TICK: f32 = 1.0 / 60.0;
+pub const MOVEMENT_SPEED: f32 = 200.0;
+pub const FIRE_COOLDOWN: f32 = 0.25;
+pub const RESPAWN_INVULNERABILITY: f32 = 2.0;
+const SHIELD_INVULNERABILITY: f32 = 0.5;
+const DASH_DURATION: f32 = 0.15;
+const DASH_COOLDOWN: f32 = 1.0;
+const DASH_SPEED_MULTIPLIER: f32 = 3.0;
+/// Enemies above this size count as big ones.
+pub const BIG_ENEMY_SIZE: f32 = 50.0;
+pub const BULLET_DAMAGE: u32 = 10;
+/// Health of regular enemies, which go down in one hit. Big ones take two.
+const ENEMY_HEALTH: u32 = BULLET_DAMAGE;
+const BIG_ENEMY_HEALTH: u32 = 2 * BULLET_DAMAGE;
+/// Gap between the top of the playfield and a boss once it has flown in.
+const BOSS_MARGIN: f32 = 40.0;
+/// Seconds taken off a time limit instead of a life.
+pub const DEATH_PENALTY: f32 = 10.0;
+/// Pacifist points for every second a ship stays alive.
+const SURVIVAL_POINTS: u32 = 10;
+/// Pacifist points for an enemy passing a ship by less than
+/// `NEAR_MISS_DISTANCE`, chained like kills.
+const NEAR_MISS_POINTS: u32 = 25;
+const NEAR_MISS_DISTANCE: f32 = 24.0;
+/// Pacifist enemies spawned per second at the start, and how much that
+/// grows every second after.
+const PACIFIST_SPAWN_RATE: f32 = 1.0;
+const PACIFIST_SPAWN_RAMP: f32 = 0.02;
+const PACIFIST_MAX_SPAWN_RATE: f32 = 10.0;
+/// How fast pacifist enemies drift sideways towards the nearest ship.
+const HOMING_SPEED: f32 = 25.0;
+
+/// Something that happened during a tick. `player` is an index into
+/// `Playfield::players`.
+pub enum Event {
+    Shot {
+        player: usize,
+    },
+    Explosion {
+        position: Vec2,
+        size: f32,
+    },
+    /// `bomb` is set for kills by bomb rather than by bullet. The kill
+    /// scored `points` times `multiplier`.
+    EnemyKilled {
+        position: Vec2,
+        size: f32,
+        player: usize,
+        bomb: bool,
+        points: u32,
+        multiplier: u32,
+    },
+    /// A bullet hit an enemy that survived it.
+    EnemyDamaged {
+        position: Vec2,
+        player: usize,
+        damage: u32,
+    },
+    /// A ship ran into an enemy of `size`, whether or not the shield took
+    /// the hit.
+    PlayerHit {
+        player: usize,
+        size: f32,
+    },
+    WaveCleared,
+    BossSpawned,
+    /// The level's boss was destroyed, which clears the level.
+    BossDefeated,
+    /// A hit that would have cost a life took time off the clock instead.
+    TimeLost {
+        player: usize,
+        position: Vec2,
+    },
+    /// An enemy narrowly missed a ship in pacifist mode.
+    NearMiss {
+        position: Vec2,
+        player: usize,
+        points: u32,
+        multiplier: u32,
+    },
+}
+
+#[derive(Clone, Debug)]
+pub struct Playfield {
+    pub size: Vec2,
+    pub players: Vec<Player>,
+    pub squares: Vec<Shape>,
+    /// Each bullet remembers the index of the player that fired it.
+    pub bullets: Vec<(Shape, usize)>,
+    pub credits: u32,
+    pub wave: u32,
+    pub wave_spawned: u32,
+    /// Set once every enemy of the wave has spawned and left the field.
+    /// Spawning stays paused until `start_next_wave`.
+    pub wave_cleared: bool,
+    /// Extra enemies sent over by a versus opponent, still to spawn.
+    pub incoming_garbage: u32,
+    pub fire_cooldown: f32,
+    /// Number of ticks simulated so far.
+    pub tick: u64,
+    /// Seed the run started from, for replaying it.
+    pub seed: u64,
+    /// Scripted waves and boss of a campaign level, `None` for endless
+    /// waves.
+    pub level: Option<Level>,
+    /// The level's boss while it is on the field. It isn't one of the
+    /// `squares`: it stays until destroyed and shrugs off bombs.
+    pub boss: Option<Shape>,
+    /// Health the boss started with.
+    pub boss_max_health: u32,
+    /// Set once the level's boss is destroyed.
+    pub level_cleared: bool,
+    /// Ticks until the next scripted or pacifist spawn.
+    spawn_timer: u32,
+    /// Sideways direction of the boss, 1 or -1.
+    boss_direction: f32,
+    /// Ticks between the boss's escorts, 0 for none, and until the next.
+    escort_interval: u32,
+    escort_timer: u32,
+    /// Ticks left on the clock of a timed run. Losing a life costs time
+    /// instead, and the run ends when the clock runs out.
+    pub time_left: Option<u64>,
+    /// Firing is off and score comes from surviving and near misses.
+    /// Enemies spawn ever faster instead of in waves, and home in on ships.
+    pub pacifist: bool,
+    /// Sandbox for trying things out: nothing spawns by itself and there is
+    /// no shop, enemies, waves and bosses are sent in on request.
+    pub practice: bool,
+    /// Ships take no hits. Only offered in practice.
+    pub invincible: bool,
+    rng: Rng,
+}
+
+impl Playfield {
+    pub fn new(seed: u64, size: Vec2) -> Playfield {
+        Playfield {
+            size,
+            players: vec![],
+            squares: vec![],
+            bullets: vec![],
+            credits: 0,
+            wave: 1,
+            wave_spawned: 0,
+            wave_cleared: false,
+            incoming_garbage: 0,
+            fire_cooldown: FIRE_COOLDOWN,
+            tick: 0,
+            seed,
+            level: None,
+            boss: None,
+            boss_max_health: 0,
+            level_cleared: false,
+            spawn_timer: 0,
+            boss_direction: 1.0,
+            escort_interval: 0,
+            escort_timer: 0,
+            time_left: None,
+            pacifist: false,
+            practice: false,
+            invincible: false,
+            rng: Rng::new(seed),
+        }
+    }
+
+    /// A playfield running the waves and boss of `level`.
+    pub fn with_level(seed: u64, size: Vec2, level: Level) -> Playfield {
+        Playfield {
+            level: Some(level),
+            ..Playfield::new(seed, size)
+        }
+    }
+
+    /// A playfield with endless waves against a clock of `seconds`.
+    pub fn with_time_limit(seed: u64, size: Vec2, seconds: f32) -> Playfield {
+        Playfield {
+            time_left: Some(ticks(seconds)),
+            ..Playfield::new(seed, size)
+        }
+    }
+
+    /// A playfield for pacifist survival.
+    pub fn pacifist(seed: u64, size: Vec2) -> Playfield {
+        Playfield {
+            pacifist: true,
+            ..Playfield::new(seed, size)
+        }
+    }
+
+    /// An empty playfield for practice.
+    pub fn practice(seed: u64, size: Vec2) -> Playfield {
+        let mut field = Playfield {
+            practice: true,
+            ..Playfield::new(seed, size)
+        };
+        // Nothing left to spawn until a wave is sent
+        field.wave_spawned = field.wave_size();
+        field
+    }
+
+    /// Starts spawning a wave of the current size, in practice.
+    pub fn send_wave(&mut self) {
+        self.wave_spawned = 0;
+    }
+
+    /// Enemies in the current wave. The boss wave of a level has none
+    /// besides the boss.
+    pub fn wave_size(&self) -> u32 {
+        match &self.level {
+            Some(level) => level
+                .waves
+                .get(self.wave as usize - 1)
+                .map_or(0, |wave| wave.count),
+            None => 10 + self.wave * 5,
+        }
+    }
+
+    pub fn game_over(&self) -> bool {
+        self.time_left == Some(0) || self.players.iter().all(|player| !player.alive())
+    }
+
+    pub fn best_score(&self) -> u32 {
+        self.players
+            .iter()
+            .map(|player| player.score)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Adds the next player, spawning near the bottom centre.
+    pub fn join(&mut self) {
+        self.join_as(self.players.len());
+    }
+
+    /// Adds a player that reads its input from `slot`.
+    pub fn join_as(&mut self, slot: usize) {
+        let speed = self
+            .players
+            .first()
+            .map_or(MOVEMENT_SPEED, |player| player.ship.speed);
+        let x = self.size.x / 2.0 + self.players.len() as f32 * 64.0;
+        let mut player = Player::new(slot, vec2(x, self.size.y - 64.0), speed);
+        if !self.players.is_empty() {
+            player.invulnerable = RESPAWN_INVULNERABILITY;
+        }
+        self.players.push(player);
+    }
+
+    pub fn start_next_wave(&mut self) {
+        self.wave += 1;
+        self.wave_spawned = 0;
+        self.wave_cleared = false;
+        self.bullets.clear();
+    }
+
+    /// Advances one tick. `inputs` is indexed by player slot; missing
+    /// entries count as no buttons held.
+    pub fn step(&mut self, inputs: &[PlayerInput], events: &mut Vec<Event>) {
+        self.tick += 1;
+        let delta_time = TICK;
+        if let Some(time_left) = &mut self.time_left {
+            *time_left = time_left.saturating_sub(1);
+        }
+
+        for (index, player) in self.players.iter_mut().enumerate() {
+            if !player.alive() {
+                continue;
+            }
+            let input = inputs.get(player.slot).copied().unwrap_or_default();
+            player.invulnerable -= delta_time;
+            player.fire_timer -= delta_time;
+            player.dash -= delta_time;
+            player.dash_cooldown -= delta_time;
+            player.update_chain(delta_time);
+
+            // Dash is a short burst of speed that passes through enemies
+            if input.pressed(player.input, Button::Dash) && player.dash_cooldown <= 0.0 {
+                player.dash = DASH_DURATION;
+                player.dash_cooldown = DASH_COOLDOWN;
+                player.invulnerable = player.invulnerable.max(DASH_DURATION);
+            }
+
+            let ship = &mut player.ship;
+            let speed = if player.dash > 0.0 {
+                ship.speed * DASH_SPEED_MULTIPLIER
+            } else {
+                ship.speed
+            };
+            let movement = input.movement();
+            ship.x += movement.x * speed * delta_time;
+            ship.y += movement.y * speed * delta_time;
+
+            // Keep ship on the playfield
+            ship.x = clamp(
+                ship.x,
+                0.0 + (ship.size / 2.0),
+                self.size.x - (ship.size / 2.0),
+            );
+            ship.y = clamp(
+                ship.y,
+                0.0 + (ship.size / 2.0),
+                self.size.y - (ship.size / 2.0),
+            );
+
+            // Shoot
+            if input.held(Button::Fire) && player.fire_timer <= 0.0 && !self.pacifist {
+                player.fire_timer = self.fire_cooldown;
+                self.bullets.push((
+                    Shape {
+                        x: ship.x,
+                        y: ship.y - 24.0,
+                        speed: ship.speed * 2.0,
+                        size: 32.0,
+                        collided: false,
+                        health: 0,
+                    },
+                    index,
+                ));
+                events.push(Event::Shot { player: index });
+            }
+
+            // Bomb clears every enemy on the playfield
+            if input.pressed(player.input, Button::Bomb) && player.bombs > 0 {
+                player.bombs -= 1;
+                // Squares shot down last tick are still here until the
+                // clean-up below, but they've been paid for
+                for square in self.squares.iter_mut().filter(|square| !square.collided) {
+                    square.collided = true;
+                    let points = square.size.round() as u32;
+                    let multiplier = player.multiplier();
+                    player.score_kill(points);
+                    self.credits += (square.size / 16.0).round() as u32;
+                    let position = vec2(square.x, square.y);
+                    events.push(Event::Explosion {
+                        position,
+                        size: square.size,
+                    });
+                    events.push(Event::EnemyKilled {
+                        position,
+                        size: square.size,
+                        player: index,
+                        bomb: true,
+                        points,
+                        multiplier,
+                    });
+                }
+            }
+
+            player.input = input;
+        }
+
+        if self.level.is_some() {
+            self.step_level(events);
+        } else if self.pacifist {
+            self.step_pacifist();
+        } else if self.wave_spawned < self.wave_size() && self.rng.range_u32(0, 99) >= 95 {
+            // Generate a new square until the wave is fully spawned
+            self.wave_spawned += 1;
+            self.spawn_enemy((16.0, 64.0), (50.0, 150.0));
+        }
+        if self.boss.is_some() {
+            self.step_boss();
+        }
+
+        // Garbage falls faster than regular enemies
+        if self.incoming_garbage > 0 && self.rng.range_u32(0, 20) == 0 {
+            self.incoming_garbage -= 1;
+            let size = self.rng.range_f32(24.0, 40.0);
+            let speed = self.rng.range_f32(150.0, 250.0);
+            let x = self.rng.range_f32(size / 2.0, self.size.x - size / 2.0);
+            self.squares.push(Shape {
+                size,
+                speed,
+                x,
+                y: -size,
+                collided: false,
+                health: enemy_health(size),
+            });
+        }
+
+        // Move squares
+        for square in &mut self.squares {
+            let previous_y = square.y;
+            square.y += square.speed * delta_time;
+            if !self.pacifist {
+                continue;
+            }
+            // Drift towards the nearest ship still below
+            let target = self
+                .players
+                .iter()
+                .filter(|player| player.alive() && player.ship.y > square.y)
+                .map(|player| player.ship.x)
+                .min_by(|a, b| (a - square.x).abs().total_cmp(&(b - square.x).abs()));
+            if let Some(target) = target {
+                let step = HOMING_SPEED * delta_time;
+                square.x += (target - square.x).clamp(-step, step);
+            }
+            // Score enemies that pass a ship closely without touching it
+            for (index, player) in self.players.iter_mut().enumerate() {
+                let ship = vec2(player.ship.x, player.ship.y);
+                let gap = (square.x - ship.x).abs() - (square.size + player.ship.size) / 2.0;
+                if player.alive()
+                    && previous_y < ship.y
+                    && square.y >= ship.y
+                    && gap > 0.0
+                    && gap < NEAR_MISS_DISTANCE
+                {
+                    let multiplier = player.multiplier();
+                    // Near misses chain like kills
+                    player.score_kill(NEAR_MISS_POINTS);
+                    events.push(Event::NearMiss {
+                        position: ship,
+                        player: index,
+                        points: NEAR_MISS_POINTS,
+                        multiplier,
+                    });
+                }
+            }
+        }
+
+        // Move bullets
+        for (bullet, _) in &mut self.bullets {
+            bullet.y -= bullet.speed * delta_time;
+        }
+
+        // Remove squares and bullets when they go off the playfield or have collided
+        let height = self.size.y;
+        self.squares
+            .retain(|square| square.y < height + square.size && !square.collided);
+        self.bullets
+            .retain(|(bullet, _)| bullet.y > 0.0 - bullet.size / 2.0 && !bullet.collided);
+
+        // Check collisions
+        for (index, player) in
+            self.players.iter_mut().enumerate().filter(|(_, player)| {
+                player.alive() && player.invulnerable <= 0.0 && !self.invincible
+            })
+        {
+            let size = if let Some(boss) = self
+                .boss
+                .as_ref()
+                .filter(|boss| player.ship.collides_with(boss))
+            {
+                boss.size
+            } else if let Some(square) = self
+                .squares
+                .iter_mut()
+                .find(|square| !square.collided && player.ship.collides_with(square))
+            {
+                square.collided = true;
+                events.push(Event::Explosion {
+                    position: vec2(square.x, square.y),
+                    size: square.size,
+                });
+                square.size
+            } else {
+                continue;
+            };
+            events.push(Event::PlayerHit {
+                player: index,
+                size,
+            });
+            // Any hit costs the chain, even one the shield absorbs
+            player.break_chain();
+            if player.shield > 0 {
+                player.shield -= 1;
+                player.invulnerable = SHIELD_INVULNERABILITY;
+            } else if let Some(time_left) = &mut self.time_left {
+                *time_left = time_left.saturating_sub(ticks(DEATH_PENALTY));
+                player.invulnerable = RESPAWN_INVULNERABILITY;
+                events.push(Event::TimeLost {
+                    player: index,
+                    position: vec2(player.ship.x, player.ship.y),
+                });
+            } else {
+                player.lives -= 1;
+                player.invulnerable = RESPAWN_INVULNERABILITY;
+            }
+        }
+
+        for square in self.squares.iter_mut().chain(self.boss.as_mut()) {
+            for (bullet, owner) in self.bullets.iter_mut() {
+                if square.collided || bullet.collided || !bullet.collides_with(square) {
+                    continue;
+                }
+                bullet.collided = true;
+                let position = vec2(square.x, square.y);
+                square.health = square.health.saturating_sub(BULLET_DAMAGE);
+                if square.health > 0 {
+                    events.push(Event::EnemyDamaged {
+                        position,
+                        player: *owner,
+                        damage: BULLET_DAMAGE,
+                    });
+                    continue;
+                }
+                square.collided = true;
+                let player = &mut self.players[*owner];
+                let points = square.size.round() as u32;
+                let multiplier = player.multiplier();
+                player.score_kill(points);
+                self.credits += (square.size / 16.0).round() as u32;
+                events.push(Event::Explosion {
+                    position,
+                    size: square.size,
+                });
+                events.push(Event::EnemyKilled {
+                    position,
+                    size: square.size,
+                    player: *owner,
+                    bomb: false,
+                    points,
+                    multiplier,
+                });
+            }
+        }
+
+        if self.boss.as_ref().is_some_and(|boss| boss.collided) {
+            self.boss = None;
+            self.level_cleared = self.level.is_some();
+            events.push(Event::BossDefeated);
+        }
+
+        if !self.wave_cleared
+            && !self.pacifist
+            && !self.practice
+            && !self.level_cleared
+            && self.boss.is_none()
+            && self.wave_spawned == self.wave_size()
+            && self.incoming_garbage == 0
+            && self.squares.iter().all(|square| square.collided)
+        {
+            self.wave_cleared = true;
+            events.push(Event::WaveCleared);
+        }
+    }
+
+    /// Spawns a square at a random position above the playfield.
+    pub fn spawn_enemy(&mut self, size: (f32, f32), speed: (f32, f32)) {
+        let size = self.rng.range_f32(size.0, size.1);
+        let speed = self.rng.range_f32(speed.0, speed.1);
+        let x = self.rng.range_f32(size / 2.0, self.size.x - size / 2.0);
+        self.squares.push(Shape {
+            size,
+            speed,
+            x,
+            y: -size,
+            collided: false,
+            health: enemy_health(size),
+        });
+    }
+
+    /// Awards survival points every second and spawns enemies at a rate
+    /// that keeps climbing.
+    fn step_pacifist(&mut self) {
+        if self.tick.is_multiple_of(ticks(1.0)) {
+            for player in self.players.iter_mut().filter(|player| player.alive()) {
+                player.score += SURVIVAL_POINTS;
+            }
+        }
+        self.spawn_timer = self.spawn_timer.saturating_sub(1);
+        if self.spawn_timer == 0 {
+            let seconds = self.tick as f32 * TICK;
+            let rate =
+                (PACIFIST_SPAWN_RATE + seconds * PACIFIST_SPAWN_RAMP).min(PACIFIST_MAX_SPAWN_RATE);
+            self.spawn_timer = ticks(1.0 / rate) as u32;
+            self.spawn_enemy((16.0, 64.0), (50.0, 150.0));
+        }
+    }
+
+    /// Spawns the level's scripted waves at their own pace, then its boss.
+    fn step_level(&mut self, events: &mut Vec<Event>) {
+        let Some(level) = &self.level else {
+            return;
+        };
+        let wave = level.waves.get(self.wave as usize - 1).copied();
+        let script = level.boss;
+        self.spawn_timer = self.spawn_timer.saturating_sub(1);
+
+        if let Some(wave) = wave {
+            if self.wave_spawned < wave.count && self.spawn_timer == 0 {
+                self.wave_spawned += 1;
+                self.spawn_timer = wave.interval;
+                self.spawn_enemy(wave.size, wave.speed);
+            }
+            return;
+        }
+        if self.boss.is_none() && !self.level_cleared && !self.wave_cleared {
+            self.spawn_boss(script);
+            events.push(Event::BossSpawned);
+        }
+    }
+
+    /// Sends in a boss flying like `script`, replacing any already here.
+    pub fn spawn_boss(&mut self, script: BossScript) {
+        self.boss = Some(Shape {
+            size: script.size,
+            speed: script.speed,
+            x: self.size.x / 2.0,
+            y: -script.size / 2.0,
+            collided: false,
+            health: script.health,
+        });
+        self.boss_max_health = script.health;
+        self.escort_interval = script.escort_interval;
+        self.escort_timer = script.escort_interval;
+    }
+
+    /// Flies the boss in and sways it from side to side, sending escorts
+    /// down.
+    fn step_boss(&mut self) {
+        let Some(boss) = &mut self.boss else {
+            return;
+        };
+        let hover = BOSS_MARGIN + boss.size / 2.0;
+        if boss.y < hover {
+            boss.y = (boss.y + boss.speed * TICK).min(hover);
+        } else {
+            boss.x += self.boss_direction * boss.speed * TICK;
+            let half = boss.size / 2.0;
+            if boss.x < half || boss.x > self.size.x - half {
+                boss.x = boss.x.clamp(half, (self.size.x - half).max(half));
+                self.boss_direction = -self.boss_direction;
+            }
+        }
+        self.escort_timer = self.escort_timer.saturating_sub(1);
+        if self.escort_interval > 0 && self.escort_timer == 0 {
+            self.escort_timer = self.escort_interval;
+            let (x, y) = (boss.x, boss.y + boss.size / 2.0);
+            self.spawn_enemy((16.0, 24.0), (120.0, 160.0));
+            let escort = self.squares.last_mut().unwrap();
+            escort.x = x;
+            escort.y = y;
+        }
+    }
+
+    /// FNV-1a over the whole state, for comparing peers.
+    pub fn checksum(&self) -> u64 {
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        let mut feed = |value: u64| {
+            for byte in value.to_le_bytes() {
+                hash ^= u64::from(byte);
+                hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+            }
+        };
+        feed(self.tick);
+        feed(self.time_left.unwrap_or(u64::MAX));
+        feed(u64::from(self.spawn_timer));
+        feed(u64::from(self.escort_timer));
+        feed(self.rng.clone().next_u64());
+        feed(u64::from(self.credits));
+        feed(u64::from(self.wave));
+        feed(u64::from(self.wave_spawned));
+        feed(u64::from(self.incoming_garbage));
+        for player in &self.players {
+            feed(u64::from(player.ship.x.to_bits()));
+            feed(u64::from(player.ship.y.to_bits()));
+            feed(u64::from(player.score));
+            feed(u64::from(player.lives));
+            feed(u64::from(player.shield));
+            feed(u64::from(player.bombs));
+            feed(u64::from(player.dash_cooldown.to_bits()));
+            feed(u64::from(player.chain));
+            feed(u64::from(player.chain_timer.to_bits()));
+        }
+        for square in &self.squares {
+            feed(u64::from(square.x.to_bits()));
+            feed(u64::from(square.y.to_bits()));
+            feed(u64::from(square.size.to_bits()));
+            feed(u64::from(square.health));
+        }
+        if let Some(boss) = &self.boss {
+            feed(u64::from(boss.x.to_bits()));
+            feed(u64::from(boss.y.to_bits()));
+            feed(u64::from(boss.health));
+        }
+        for (bullet, owner) in &self.bullets {
+            feed(u64::from(bullet.x.to_bits()));
+            feed(u64::from(bullet.y.to_bits()));
+            feed(*owner as u64);
+        }
+        hash
+    }
+}
+
+fn ticks(seconds: f32) -> u64 {
+    (seconds / TICK).round() as u64
+}
+
+fn enemy_health(size: f32) -> u32 {
+    if size > BIG_ENEMY_SIZE {
+        BIG_ENEMY_HEALTH
+    } else {
+        ENEMY_HEALTH
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target(x: f32, y: f32, health: u32) -> Shape {
+        Shape {
+            size: 32.0,
+            speed: 0.0,
+            x,
+            y,
+            collided: false,
+            health,
+        }
+    }
+
+    #[test]
+    fn bomb_skips_squares_already_shot_down() {
+        let mut field = Playfield::practice(1, vec2(800.0, 600.0));
+        field.join();
+        field.players[0].bombs = 1;
+        field.squares.push(target(100.0, 100.0, ENEMY_HEALTH));
+        field.bullets.push((target(100.0, 100.0, 0), 0));
+
+        let mut events = vec![];
+        field.step(&[PlayerInput::default()], &mut events);
+        let (score, credits) = (field.players[0].score, field.credits);
+        assert!(score > 0);
+        assert!(credits > 0);
+
+        let mut bomb = PlayerInput::default();
+        bomb.set(Button::Bomb, true);
+        events.clear();
+        field.step(&[bomb], &mut events);
+        assert_eq!(field.players[0].bombs, 0);
+        assert_eq!(field.players[0].score, score);
+        assert_eq!(field.credits, credits);
+        assert!(
+            !events
+                .iter()
+                .any(|event| matches!(event, Event::EnemyKilled { .. }))
+        );
+    }
+
+    #[test]
+    fn big_enemies_take_two_hits() {
+        let mut field = Playfield::practice(1, vec2(800.0, 600.0));
+        field.join();
+        field.spawn_enemy((60.0, 60.0), (0.0, 0.0));
+        field.squares[0].y = 100.0;
+        let position = vec2(field.squares[0].x, 100.0);
+
+        let mut events = vec![];
+        field.bullets.push((target(position.x, position.y, 0), 0));
+        field.step(&[PlayerInput::default()], &mut events);
+        assert!(events.iter().any(|event| matches!(
+            event,
+            Event::EnemyDamaged { position: at, damage: BULLET_DAMAGE, .. } if *at == position
+        )));
+        assert_eq!(field.players[0].score, 0);
+
+        events.clear();
+        field.bullets.push((target(position.x, position.y, 0), 0));
+        field.step(&[PlayerInput::default()], &mut events);
+        assert!(events.iter().any(
+            |event| matches!(event, Event::EnemyKilled { position: at, .. } if *at == position)
+        ));
+        assert!(field.players[0].score > 0);
+    }
+}
@@ -0,0 +1,124 @@
+//! Floating text over the playfield: the points a kill scored, with its
+//! multiplier, and the damage dealt to enemies that take more than one hit.
+//! Each popup rises and fades where it appeared. They are all drawn
+//! together in one font size, so macroquad batches them into a handful of
+//! draw calls however many are on screen.
+
+use macroquad::prelude::*;
+
+use crate::playfield::{DEATH_PENALTY, Event};
+
+/// Seconds a popup stays on screen.
+const LIFETIME: f32 = 0.8;
+/// Pixels per second a popup rises.
+const RISE_SPEED: f32 = 60.0;
+/// The oldest popups make way beyond this many.
+const MAX_POPUPS: usize = 256;
+/// Rasterized once; popups are resized with `font_scale`.
+const FONT_SIZE: u16 = 16;
+
+struct Popup {
+    text: String,
+    /// Where the popup appeared, on the playfield.
+    position: Vec2,
+    /// Text width at `scale`, for centring.
+    width: f32,
+    scale: f32,
+    color: Color,
+    age: f32,
+}
+
+pub struct Popups {
+    popups: Vec<Popup>,
+}
+
+impl Popups {
+    pub fn new() -> Popups {
+        Popups { popups: vec![] }
+    }
+
+    pub fn clear(&mut self) {
+        self.popups.clear();
+    }
+
+    /// Adds popups for kills and damage among `events`.
+    pub fn handle(&mut self, events: &[Event], font: &Font) {
+        for event in events {
+            let (text, position, scale, color) = match *event {
+                Event::EnemyKilled {
+                    position,
+                    points,
+                    multiplier,
+                    ..
+                } if multiplier > 1 => (
+                    format!("{} X{multiplier}", points * multiplier),
+                    position,
+                    1.0 + 0.1 * multiplier as f32,
+                    YELLOW,
+                ),
+                Event::EnemyKilled {
+                    position, points, ..
+                } => (points.to_string(), position, 1.0, WHITE),
+                Event::EnemyDamaged {
+                    position, damage, ..
+                } => (damage.to_string(), position, 0.75, ORANGE),
+                Event::NearMiss {
+                    position,
+                    points,
+                    multiplier,
+                    ..
+                } => (
+                    format!("CLOSE +{}", points * multiplier),
+                    position - vec2(0.0, 30.0),
+                    1.0,
+                    SKYBLUE,
+                ),
+                Event::TimeLost { position, .. } => {
+                    (format!("-{}S", DEATH_PENALTY as u32), position, 1.5, RED)
+                }
+                _ => continue,
+            };
+            let width = measure_text(&text, Some(font), FONT_SIZE, scale).width;
+            if self.popups.len() == MAX_POPUPS {
+                self.popups.remove(0);
+            }
+            self.popups.push(Popup {
+                text,
+                position,
+                width,
+                scale,
+                color,
+                age: 0.0,
+            });
+        }
+    }
+
+    pub fn update(&mut self, delta_time: f32) {
+        for popup in &mut self.popups {
+            popup.age += delta_time;
+        }
+        self.popups.retain(|popup| popup.age < LIFETIME);
+    }
+
+    /// Draws every popup, offset by `origin` like the playfield.
+    pub fn draw(&self, origin: Vec2, font: &Font) {
+        for popup in &self.popups {
+            let fade = 1.0 - popup.age / LIFETIME;
+            draw_text_ex(
+                &popup.text,
+                origin.x + popup.position.x - popup.width / 2.0,
+                origin.y + popup.position.y - popup.age * RISE_SPEED,
+                TextParams {
+                    font: Some(font),
+                    font_size: FONT_SIZE,
+                    font_scale: popup.scale,
+                    color: Color {
+                        a: popup.color.a * fade,
+                        ..popup.color
+                    },
+                    ..Default::default()
+                },
+            );
+        }
+    }
+}
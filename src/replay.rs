@@ -0,0 +1,166 @@
+//! A run recorded as its seed plus everything fed into the playfield after
+//! that, so it can be played back tick for tick. Online scores are sent
+//! with their replay so the server can check them, and the last finished
+//! run is kept in the data storage.
+//!
+//! The text is one line of space separated tokens after a
+//! `<version> <seed> <width>x<height>` header:
+//!
+//! - the hex bytes of every player's input for a tick, with `*<n>` appended
+//!   when it was held for `n` ticks in a row
+//! - `J` when a player joined
+//! - `R<width>x<height>` when the playfield was resized
+//! - `B<item>` for a shop purchase and `N` for starting the next wave
+
+use std::fmt::Write;
+use std::io;
+
+use macroquad::prelude::*;
+
+use crate::input::PlayerInput;
+use crate::persist::Storage;
+
+/// The last finished run, in the data storage.
+const LAST_REPLAY_FILE: &str = "last_run.replay";
+
+/// Bumped whenever the playfield changes in a way that plays the same
+/// inputs out differently.
+const REPLAY_VERSION: u32 = 2;
+
+pub struct Replay {
+    text: String,
+    /// Inputs of the latest ticks, not yet written because they may repeat.
+    held: Option<(String, u32)>,
+}
+
+impl Replay {
+    pub fn new(seed: u64, size: Vec2) -> Replay {
+        Replay {
+            text: format!("{REPLAY_VERSION} {seed} {}x{}", size.x, size.y),
+            held: None,
+        }
+    }
+
+    /// Records the inputs of one `Playfield::step`.
+    pub fn tick(&mut self, inputs: &[PlayerInput]) {
+        let mut token = String::with_capacity(inputs.len() * PlayerInput::SIZE * 2);
+        for byte in inputs.iter().flat_map(|input| input.to_bytes()) {
+            write!(token, "{byte:02x}").unwrap();
+        }
+        match &mut self.held {
+            Some((held, ticks)) if *held == token => *ticks += 1,
+            _ => {
+                self.flush();
+                self.held = Some((token, 1));
+            }
+        }
+    }
+
+    pub fn join(&mut self) {
+        self.command("J");
+    }
+
+    pub fn resize(&mut self, size: Vec2) {
+        self.command(&format!("R{}x{}", size.x, size.y));
+    }
+
+    pub fn buy(&mut self, item: usize) {
+        self.command(&format!("B{item}"));
+    }
+
+    pub fn next_wave(&mut self) {
+        self.command("N");
+    }
+
+    /// Keeps the replay as the last run's, replacing the one before.
+    pub fn save(&self, storage: &mut dyn Storage) -> io::Result<()> {
+        storage.write(LAST_REPLAY_FILE, &self.to_text())
+    }
+
+    /// The replay kept by `save`, or `None` if there is none or it was
+    /// recorded by a version that plays out differently.
+    pub fn load(storage: &dyn Storage) -> io::Result<Option<Replay>> {
+        let Some(text) = storage.read(LAST_REPLAY_FILE)? else {
+            return Ok(None);
+        };
+        let text = text.trim_end();
+        let version = text
+            .split(' ')
+            .next()
+            .and_then(|version| version.parse().ok());
+        Ok((version == Some(REPLAY_VERSION)).then(|| Replay {
+            text: text.to_string(),
+            held: None,
+        }))
+    }
+
+    /// The replay so far, as a single line of text.
+    pub fn to_text(&self) -> String {
+        let mut text = self.text.clone();
+        if let Some(held) = &self.held {
+            push_ticks(&mut text, held);
+        }
+        text
+    }
+
+    fn command(&mut self, token: &str) {
+        self.flush();
+        self.text.push(' ');
+        self.text.push_str(token);
+    }
+
+    fn flush(&mut self) {
+        if let Some(held) = self.held.take() {
+            push_ticks(&mut self.text, &held);
+        }
+    }
+}
+
+fn push_ticks(text: &mut String, (token, ticks): &(String, u32)) {
+    text.push(' ');
+    text.push_str(token);
+    if *ticks > 1 {
+        write!(text, "*{ticks}").unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::persist::MemoryStorage;
+
+    #[test]
+    fn round_trip() {
+        let mut storage = MemoryStorage::default();
+        assert!(Replay::load(&storage).unwrap().is_none());
+
+        let mut replay = Replay::new(42, vec2(800.0, 600.0));
+        replay.join();
+        let mut fire = PlayerInput::default();
+        fire.set(crate::input::Button::Fire, true);
+        for _ in 0..3 {
+            replay.tick(&[fire]);
+        }
+        replay.buy(1);
+        replay.next_wave();
+        replay.tick(&[PlayerInput::default()]);
+        replay.save(&mut storage).unwrap();
+
+        let loaded = Replay::load(&storage).unwrap().unwrap();
+        assert_eq!(loaded.to_text(), replay.to_text());
+        assert!(
+            loaded
+                .to_text()
+                .starts_with(&format!("{REPLAY_VERSION} 42 800x600 J "))
+        );
+    }
+
+    #[test]
+    fn older_replay_is_ignored() {
+        let mut storage = MemoryStorage::default();
+        storage
+            .write(LAST_REPLAY_FILE, "1 42 800x600 J 000000*60")
+            .unwrap();
+        assert!(Replay::load(&storage).unwrap().is_none());
+    }
+}
@@ -0,0 +1,34 @@
+//! Small seeded random generator for the simulation. Unlike macroquad's
+//! global `rand`, every `Playfield` owns one, so its state can be cloned
+//! into rollback snapshots and replays stay reproducible.
+
+#[derive(Clone, Debug)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng { state: seed }
+    }
+
+    /// SplitMix64, chosen because it is tiny and has no bad seeds.
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `low..high`.
+    pub fn range_u32(&mut self, low: u32, high: u32) -> u32 {
+        low + (self.next_u64() % u64::from(high - low)) as u32
+    }
+
+    /// Uniform in `low..high`.
+    pub fn range_f32(&mut self, low: f32, high: f32) -> f32 {
+        let unit = (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32;
+        low + unit * (high - low)
+    }
+}
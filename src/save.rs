@@ -0,0 +1,362 @@
+//! Everything the game remembers between runs, in one versioned file in
+//! the data storage. Settings live apart from it, see `config`.
+//!
+//! The file starts with a `MYGAME-SAVE <version> <checksum>` header line,
+//! followed by INI sections. The checksum is FNV-1a over everything after
+//! the header. Each write replaces the file in one go and the previous save
+//! is kept as a backup, so a crash or a bad disk never costs more than the
+//! latest change.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{self, Write};
+use std::io;
+
+use crate::ini;
+use crate::leaderboard::{self, ENDLESS_BOARD, Leaderboard};
+use crate::persist::{Storage, backup_name};
+
+pub const SAVE_FILE: &str = "save.dat";
+/// Where an unreadable save is moved so starting fresh doesn't destroy it.
+const CORRUPT_FILE: &str = "save.dat.corrupt";
+/// Files from before the save format, imported on first start.
+const LEGACY_HIGH_SCORE_FILE: &str = "highscore.dat";
+const LEGACY_LEADERBOARD_FILE: &str = "leaderboard.ini";
+
+const MAGIC: &str = "MYGAME-SAVE";
+/// 2 added leaderboards besides the endless one, which older builds would
+/// mix up with it.
+pub const SAVE_VERSION: u32 = 2;
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(io::Error),
+    /// The header is missing or mangled.
+    NotASave,
+    /// Written by a newer build of the game, which we mustn't overwrite.
+    NewerVersion(u32),
+    /// The checksum doesn't match, so the file was damaged.
+    Corrupt,
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveError::Io(err) => write!(f, "{err}"),
+            SaveError::NotASave => write!(f, "not a save file"),
+            SaveError::NewerVersion(version) => {
+                write!(f, "saved by a newer version of the game (format {version})")
+            }
+            SaveError::Corrupt => write!(f, "checksum mismatch, the file is damaged"),
+        }
+    }
+}
+
+impl From<io::Error> for SaveError {
+    fn from(err: io::Error) -> SaveError {
+        SaveError::Io(err)
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct SaveData {
+    pub high_score: u32,
+    /// Best runs by board, see `leaderboard::ENDLESS_BOARD`.
+    pub boards: BTreeMap<String, Leaderboard>,
+    /// Ids of everything unlocked so far.
+    pub unlocks: BTreeSet<String>,
+    /// Lifetime counters by name.
+    pub stats: BTreeMap<String, u64>,
+}
+
+impl SaveData {
+    /// Loads the save, falling back to the backup, and on the very first
+    /// start to the files in `legacy`. Anything the player should know
+    /// about, like a damaged save, comes back as a message.
+    pub fn load(storage: &mut dyn Storage, legacy: &dyn Storage) -> (SaveData, Option<String>) {
+        let error = match SaveData::read(storage, SAVE_FILE) {
+            Ok(Some(data)) => return (data, None),
+            Ok(None) => None,
+            Err(err) => Some(err),
+        };
+
+        if let Ok(Some(data)) = SaveData::read(storage, &backup_name(SAVE_FILE)) {
+            let message = match error {
+                Some(err) => format!("Save damaged ({err}), restored the backup"),
+                None => "Save missing, restored the backup".to_string(),
+            };
+            return (data, Some(message));
+        }
+
+        if let Some(err) = error {
+            // Refuse to touch saves from the future; anything else is moved
+            // aside before we start over
+            let message = match err {
+                SaveError::NewerVersion(_) => format!("Can't load save: {err}"),
+                _ => match storage.rename(SAVE_FILE, CORRUPT_FILE) {
+                    Ok(()) => format!("Save damaged ({err}), moved to {CORRUPT_FILE}"),
+                    Err(_) => format!("Save damaged ({err})"),
+                },
+            };
+            return (SaveData::default(), Some(message));
+        }
+
+        SaveData::migrate_legacy(storage, legacy)
+    }
+
+    fn read(storage: &dyn Storage, name: &str) -> Result<Option<SaveData>, SaveError> {
+        storage
+            .read(name)?
+            .map(|text| SaveData::from_text(&text))
+            .transpose()
+    }
+
+    /// Imports a save left in `legacy` by older builds, or else
+    /// `highscore.dat` and the old leaderboard file, then writes them out
+    /// to `storage`.
+    fn migrate_legacy(
+        storage: &mut dyn Storage,
+        legacy: &dyn Storage,
+    ) -> (SaveData, Option<String>) {
+        if let Ok(Some(data)) = SaveData::read(legacy, SAVE_FILE) {
+            let message = data
+                .write(storage)
+                .err()
+                .map(|err| format!("Importing old save: could not save: {err}"));
+            return (data, message);
+        }
+
+        let mut data = SaveData::default();
+        let mut problems = vec![];
+        let mut found = false;
+
+        if let Ok(Some(text)) = legacy.read(LEGACY_HIGH_SCORE_FILE) {
+            found = true;
+            match text.trim().parse() {
+                Ok(high_score) => data.high_score = high_score,
+                Err(_) => problems.push(format!("ignored unreadable {LEGACY_HIGH_SCORE_FILE}")),
+            }
+        }
+        if let Ok(Some(text)) = legacy.read(LEGACY_LEADERBOARD_FILE) {
+            found = true;
+            let leaderboard = Leaderboard::from_sections(&ini::parse(&text));
+            data.boards.insert(ENDLESS_BOARD.to_string(), leaderboard);
+        }
+        let best = data
+            .board(ENDLESS_BOARD)
+            .entries
+            .first()
+            .map_or(0, |entry| entry.score);
+        data.high_score = data.high_score.max(best);
+
+        if found && let Err(err) = data.write(storage) {
+            problems.push(format!("could not save: {err}"));
+        }
+        let message =
+            (!problems.is_empty()).then(|| format!("Importing old saves: {}", problems.join(", ")));
+        (data, message)
+    }
+
+    /// The leaderboard named `board`, empty until a run is entered on it.
+    pub fn board(&mut self, board: &str) -> &mut Leaderboard {
+        self.boards.entry(board.to_string()).or_default()
+    }
+
+    /// Replaces the save, keeping the previous one as a backup.
+    pub fn write(&self, storage: &mut dyn Storage) -> Result<(), SaveError> {
+        storage.write_with_backup(SAVE_FILE, &self.to_text())?;
+        Ok(())
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut body = String::new();
+        writeln!(body, "[records]\nhigh_score = {}\n", self.high_score).unwrap();
+        for (board, leaderboard) in &self.boards {
+            body.push_str(&leaderboard.to_ini(board));
+        }
+        body.push_str("[unlocks]\n");
+        for unlock in &self.unlocks {
+            writeln!(body, "{unlock} = 1").unwrap();
+        }
+        body.push_str("\n[stats]\n");
+        for (name, value) in &self.stats {
+            writeln!(body, "{name} = {value}").unwrap();
+        }
+        format!("{MAGIC} {SAVE_VERSION} {:016x}\n{body}", checksum(&body))
+    }
+
+    pub fn from_text(text: &str) -> Result<SaveData, SaveError> {
+        let (header, body) = text.split_once('\n').ok_or(SaveError::NotASave)?;
+        let mut fields = header.split_whitespace();
+        if fields.next() != Some(MAGIC) {
+            return Err(SaveError::NotASave);
+        }
+        let version: u32 = fields
+            .next()
+            .and_then(|field| field.parse().ok())
+            .ok_or(SaveError::NotASave)?;
+        let stored_checksum = fields
+            .next()
+            .and_then(|field| u64::from_str_radix(field, 16).ok())
+            .ok_or(SaveError::NotASave)?;
+        // Older formats get upgraded here as they come about
+        if version > SAVE_VERSION {
+            return Err(SaveError::NewerVersion(version));
+        }
+        if checksum(body) != stored_checksum {
+            return Err(SaveError::Corrupt);
+        }
+
+        let sections = ini::parse(body);
+        let section = |name: &str| sections.iter().find(|section| section.name == name);
+        Ok(SaveData {
+            high_score: section("records")
+                .and_then(|records| records.parse("high_score"))
+                .unwrap_or_default(),
+            boards: leaderboard::parse_boards(&sections),
+            unlocks: section("unlocks")
+                .map(|unlocks| unlocks.entries.iter().map(|(id, _)| id.clone()).collect())
+                .unwrap_or_default(),
+            stats: section("stats")
+                .map(|stats| {
+                    stats
+                        .entries
+                        .iter()
+                        .filter_map(|(name, value)| Some((name.clone(), value.parse().ok()?)))
+                        .collect()
+                })
+                .unwrap_or_default(),
+        })
+    }
+}
+
+/// FNV-1a, as used for the netcode's state checksums.
+fn checksum(text: &str) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in text.bytes() {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::leaderboard::Entry;
+    use crate::persist::MemoryStorage;
+
+    fn sample() -> SaveData {
+        let mut data = SaveData {
+            high_score: 4200,
+            ..SaveData::default()
+        };
+        data.board(ENDLESS_BOARD).insert(Entry {
+            name: "ACE".to_string(),
+            score: 4200,
+            date: "2026-01-02".to_string(),
+            duration: 95.5,
+            seed: 7,
+            ship: 1,
+        });
+        data.unlocks.insert("tutorial".to_string());
+        data.stats.insert("runs".to_string(), 3);
+        data
+    }
+
+    /// Flips a digit in the body of `name`, which breaks its checksum.
+    fn damage(storage: &mut MemoryStorage, name: &str) {
+        let text = storage.files[name].replace("4200", "4201");
+        storage.files.insert(name.to_string(), text);
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut storage = MemoryStorage::default();
+        sample().write(&mut storage).unwrap();
+        let (data, message) = SaveData::load(&mut storage, &MemoryStorage::default());
+        assert_eq!(message, None);
+        assert_eq!(data.to_text(), sample().to_text());
+    }
+
+    #[test]
+    fn damaged_save_falls_back_to_the_backup() {
+        let mut storage = MemoryStorage::default();
+        sample().write(&mut storage).unwrap();
+        let mut newer = sample();
+        newer.stats.insert("runs".to_string(), 4);
+        newer.write(&mut storage).unwrap();
+        damage(&mut storage, SAVE_FILE);
+
+        let (data, message) = SaveData::load(&mut storage, &MemoryStorage::default());
+        assert_eq!(data.to_text(), sample().to_text());
+        assert!(message.unwrap().contains("restored the backup"));
+    }
+
+    #[test]
+    fn damaged_save_without_backup_is_moved_aside() {
+        let mut storage = MemoryStorage::default();
+        storage
+            .files
+            .insert(SAVE_FILE.to_string(), sample().to_text());
+        damage(&mut storage, SAVE_FILE);
+        let damaged = storage.files[SAVE_FILE].clone();
+
+        let (data, message) = SaveData::load(&mut storage, &MemoryStorage::default());
+        assert_eq!(data.high_score, 0);
+        assert!(message.unwrap().contains(CORRUPT_FILE));
+        assert!(!storage.files.contains_key(SAVE_FILE));
+        assert_eq!(storage.files[CORRUPT_FILE], damaged);
+        assert!(matches!(
+            SaveData::from_text(&damaged),
+            Err(SaveError::Corrupt)
+        ));
+    }
+
+    #[test]
+    fn newer_save_is_left_alone() {
+        let mut storage = MemoryStorage::default();
+        let text = sample().to_text().replacen(
+            &format!("{MAGIC} {SAVE_VERSION}"),
+            &format!("{MAGIC} 99"),
+            1,
+        );
+        storage.files.insert(SAVE_FILE.to_string(), text.clone());
+
+        let (_, message) = SaveData::load(&mut storage, &MemoryStorage::default());
+        assert!(message.unwrap().contains("newer version"));
+        assert_eq!(storage.files[SAVE_FILE], text);
+        assert!(!storage.files.contains_key(CORRUPT_FILE));
+    }
+
+    #[test]
+    fn legacy_files_are_imported() {
+        let mut legacy = MemoryStorage::default();
+        legacy
+            .files
+            .insert(LEGACY_HIGH_SCORE_FILE.to_string(), "1234\n".to_string());
+        legacy.files.insert(
+            LEGACY_LEADERBOARD_FILE.to_string(),
+            "[entry]\nname = OLD\nscore = 2000\n".to_string(),
+        );
+        let mut storage = MemoryStorage::default();
+
+        let (data, message) = SaveData::load(&mut storage, &legacy);
+        assert_eq!(message, None);
+        // The old board's best beats the old high score file
+        assert_eq!(data.high_score, 2000);
+        assert_eq!(data.clone().board(ENDLESS_BOARD).entries[0].name, "OLD");
+        let (written, _) = SaveData::load(&mut storage, &MemoryStorage::default());
+        assert_eq!(written.to_text(), data.to_text());
+    }
+
+    #[test]
+    fn unreadable_legacy_high_score_is_reported() {
+        let mut legacy = MemoryStorage::default();
+        legacy
+            .files
+            .insert(LEGACY_HIGH_SCORE_FILE.to_string(), "lots".to_string());
+        let (data, message) = SaveData::load(&mut MemoryStorage::default(), &legacy);
+        assert_eq!(data.high_score, 0);
+        assert!(message.unwrap().contains(LEGACY_HIGH_SCORE_FILE));
+    }
+}
@@ -0,0 +1,273 @@
+//! Audio, video and gameplay options from the settings menu, kept in the
+//! `[settings]` section of the config file.
+
+use std::fmt::Write;
+
+use macroquad::window::{request_new_screen_size, set_fullscreen};
+
+use crate::config::Config;
+use crate::ini::Section;
+
+/// Window sizes to pick from when not in fullscreen.
+pub const WINDOW_SIZES: [(u32, u32); 5] = [
+    (800, 600),
+    (1024, 768),
+    (1280, 720),
+    (1600, 900),
+    (1920, 1080),
+];
+/// Frame rate caps to pick from, 0 being no cap.
+const FPS_CAPS: [u32; 5] = [0, 30, 60, 120, 144];
+/// Music plays at this fraction of its volume outside of a run.
+const MENU_MUSIC: f32 = 0.5;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Settings {
+    /// Volumes and the shake intensity go from 0 to 1 in tenths.
+    pub master_volume: f32,
+    pub music_volume: f32,
+    pub sfx_volume: f32,
+    pub fullscreen: bool,
+    /// Index into `WINDOW_SIZES`.
+    pub window_size: usize,
+    /// The one setting that doesn't apply live: miniquad only reads the
+    /// swap interval from `Conf` when it opens the window and has no call to
+    /// change it afterwards, so the menu marks a change as needing a
+    /// restart.
+    pub vsync: bool,
+    pub fps_cap: u32,
+    pub screen_shake: f32,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            master_volume: 1.0,
+            music_volume: 1.0,
+            sfx_volume: 1.0,
+            fullscreen: false,
+            window_size: 0,
+            vsync: true,
+            fps_cap: 0,
+            screen_shake: 1.0,
+        }
+    }
+}
+
+impl Settings {
+    pub fn from_section(section: &Section) -> Result<Settings, String> {
+        let mut settings = Settings::default();
+        for (key, value) in &section.entries {
+            let invalid = || format!("invalid {key} {value} in [settings]");
+            let fraction = || {
+                value
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|fraction| (0.0..=1.0).contains(fraction))
+                    .ok_or_else(invalid)
+            };
+            let flag = || value.parse::<bool>().map_err(|_| invalid());
+            match key.as_str() {
+                "master_volume" => settings.master_volume = fraction()?,
+                "music_volume" => settings.music_volume = fraction()?,
+                "sfx_volume" => settings.sfx_volume = fraction()?,
+                "fullscreen" => settings.fullscreen = flag()?,
+                "window_size" => {
+                    settings.window_size = WINDOW_SIZES
+                        .iter()
+                        .position(|(width, height)| *value == format!("{width}x{height}"))
+                        .ok_or_else(invalid)?
+                }
+                "vsync" => settings.vsync = flag()?,
+                "fps_cap" => settings.fps_cap = value.parse().map_err(|_| invalid())?,
+                "screen_shake" => settings.screen_shake = fraction()?,
+                _ => return Err(format!("unknown setting {key}")),
+            }
+        }
+        Ok(settings)
+    }
+
+    pub fn to_ini(&self) -> String {
+        let (width, height) = self.window_size();
+        let mut text = String::new();
+        writeln!(text, "[settings]").unwrap();
+        writeln!(text, "master_volume = {}", self.master_volume).unwrap();
+        writeln!(text, "music_volume = {}", self.music_volume).unwrap();
+        writeln!(text, "sfx_volume = {}", self.sfx_volume).unwrap();
+        writeln!(text, "fullscreen = {}", self.fullscreen).unwrap();
+        writeln!(text, "window_size = {width}x{height}").unwrap();
+        writeln!(text, "vsync = {}", self.vsync).unwrap();
+        writeln!(text, "fps_cap = {}", self.fps_cap).unwrap();
+        writeln!(text, "screen_shake = {}\n", self.screen_shake).unwrap();
+        text
+    }
+
+    pub fn window_size(&self) -> (u32, u32) {
+        WINDOW_SIZES[self.window_size]
+    }
+
+    /// Volume of the theme music, which is turned down in the menus.
+    pub fn music(&self, in_menu: bool) -> f32 {
+        let menu = if in_menu { MENU_MUSIC } else { 1.0 };
+        self.master_volume * self.music_volume * menu
+    }
+
+    pub fn sfx(&self) -> f32 {
+        self.master_volume * self.sfx_volume
+    }
+
+    /// Switches to fullscreen or the chosen window size.
+    pub fn apply_window(&self) {
+        set_fullscreen(self.fullscreen);
+        if !self.fullscreen {
+            let (width, height) = self.window_size();
+            request_new_screen_size(width as f32, height as f32);
+        }
+    }
+}
+
+/// Rows of the settings menu.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Setting {
+    MasterVolume,
+    MusicVolume,
+    SfxVolume,
+    Fullscreen,
+    WindowSize,
+    Vsync,
+    FpsCap,
+    ScreenShake,
+    ControlScheme,
+}
+
+impl Setting {
+    pub const ALL: [Setting; 9] = [
+        Setting::MasterVolume,
+        Setting::MusicVolume,
+        Setting::SfxVolume,
+        Setting::Fullscreen,
+        Setting::WindowSize,
+        Setting::Vsync,
+        Setting::FpsCap,
+        Setting::ScreenShake,
+        Setting::ControlScheme,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Setting::MasterVolume => "Master volume",
+            Setting::MusicVolume => "Music volume",
+            Setting::SfxVolume => "Sound effects",
+            Setting::Fullscreen => "Fullscreen",
+            Setting::WindowSize => "Window size",
+            Setting::Vsync => "Vsync",
+            Setting::FpsCap => "FPS cap",
+            Setting::ScreenShake => "Screen shake",
+            Setting::ControlScheme => "Control scheme",
+        }
+    }
+
+    /// Current value of this row in `config`. `vsync` is the value the
+    /// window was opened with, to flag a change that needs a restart.
+    pub fn value(self, config: &Config, vsync: bool) -> String {
+        let settings = &config.settings;
+        let percent = |fraction: f32| {
+            if fraction == 0.0 {
+                "Off".to_string()
+            } else {
+                format!("{}%", (fraction * 100.0).round())
+            }
+        };
+        let on_off = |flag: bool| if flag { "On" } else { "Off" };
+        match self {
+            Setting::MasterVolume => percent(settings.master_volume),
+            Setting::MusicVolume => percent(settings.music_volume),
+            Setting::SfxVolume => percent(settings.sfx_volume),
+            Setting::Fullscreen => on_off(settings.fullscreen).to_string(),
+            Setting::WindowSize => {
+                let (width, height) = settings.window_size();
+                format!("{width}x{height}")
+            }
+            Setting::Vsync if settings.vsync != vsync => {
+                format!("{} (restart)", on_off(settings.vsync))
+            }
+            Setting::Vsync => on_off(settings.vsync).to_string(),
+            Setting::FpsCap => match settings.fps_cap {
+                0 => "Unlimited".to_string(),
+                fps => fps.to_string(),
+            },
+            Setting::ScreenShake => percent(settings.screen_shake),
+            Setting::ControlScheme => config.bindings.scheme.name().to_string(),
+        }
+    }
+
+    /// Moves this row of `config` one step up or down, wrapping around for
+    /// choices and stopping at the ends for amounts.
+    pub fn step(self, config: &mut Config, up: bool) {
+        let settings = &mut config.settings;
+        let fraction = |value: &mut f32| {
+            let tenths = (*value * 10.0).round() + if up { 1.0 } else { -1.0 };
+            *value = tenths.clamp(0.0, 10.0) / 10.0;
+        };
+        let cycle = |index: usize, len: usize| {
+            if up {
+                (index + 1) % len
+            } else {
+                (index + len - 1) % len
+            }
+        };
+        match self {
+            Setting::MasterVolume => fraction(&mut settings.master_volume),
+            Setting::MusicVolume => fraction(&mut settings.music_volume),
+            Setting::SfxVolume => fraction(&mut settings.sfx_volume),
+            Setting::Fullscreen => settings.fullscreen = !settings.fullscreen,
+            Setting::WindowSize => {
+                settings.window_size = cycle(settings.window_size, WINDOW_SIZES.len())
+            }
+            Setting::Vsync => settings.vsync = !settings.vsync,
+            Setting::FpsCap => {
+                // A hand-edited cap that isn't in the list starts over
+                let index = FPS_CAPS.iter().position(|fps| *fps == settings.fps_cap);
+                settings.fps_cap = FPS_CAPS[cycle(index.unwrap_or(0), FPS_CAPS.len())];
+            }
+            Setting::ScreenShake => fraction(&mut settings.screen_shake),
+            Setting::ControlScheme => {
+                let scheme = &mut config.bindings.scheme;
+                *scheme = if up { scheme.next() } else { scheme.previous() };
+            }
+        }
+        if matches!(self, Setting::Fullscreen | Setting::WindowSize) {
+            config.settings.apply_window();
+        }
+    }
+}
+
+/// Sleeps away what is left of each frame under the FPS cap. The browser
+/// paces the web build, so there it does nothing.
+pub struct FrameLimiter {
+    #[cfg(not(target_arch = "wasm32"))]
+    last_frame: std::time::Instant,
+}
+
+impl FrameLimiter {
+    pub fn new() -> FrameLimiter {
+        FrameLimiter {
+            #[cfg(not(target_arch = "wasm32"))]
+            last_frame: std::time::Instant::now(),
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn wait(&mut self, fps_cap: u32) {
+        if fps_cap > 0 {
+            let frame = std::time::Duration::from_secs_f64(1.0 / fps_cap as f64);
+            if let Some(left) = frame.checked_sub(self.last_frame.elapsed()) {
+                std::thread::sleep(left);
+            }
+        }
+        self.last_frame = std::time::Instant::now();
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn wait(&mut self, _fps_cap: u32) {}
+}
@@ -0,0 +1,99 @@
+//! Between-wave upgrade shop. The inventory lives in `assets/shop.ini` so
+//! prices and effects can be tuned without recompiling.
+
+use crate::ini;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Upgrade {
+    FireRate,
+    Speed,
+    ShieldCapacity,
+    Bomb,
+    ExtraLife,
+}
+
+impl Upgrade {
+    fn from_id(id: &str) -> Option<Upgrade> {
+        match id {
+            "fire_rate" => Some(Upgrade::FireRate),
+            "speed" => Some(Upgrade::Speed),
+            "shield" => Some(Upgrade::ShieldCapacity),
+            "bomb" => Some(Upgrade::Bomb),
+            "extra_life" => Some(Upgrade::ExtraLife),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ShopItem {
+    pub upgrade: Upgrade,
+    pub name: String,
+    /// Price of the first purchase.
+    pub price: u32,
+    /// Each purchase multiplies the price by this factor.
+    pub price_growth: f32,
+    /// Zero means the item can be bought any number of times.
+    pub max_purchases: u32,
+    /// Strength of one purchase; its meaning depends on `upgrade`.
+    pub value: f32,
+}
+
+impl ShopItem {
+    pub fn price(&self, purchases: u32) -> u32 {
+        (self.price as f32 * self.price_growth.powi(purchases as i32)).round() as u32
+    }
+
+    pub fn sold_out(&self, purchases: u32) -> bool {
+        self.max_purchases != 0 && purchases >= self.max_purchases
+    }
+}
+
+/// Reads the shop inventory. Sections with an unknown upgrade id are skipped
+/// and missing keys fall back to defaults, so a typo never stops the game.
+pub fn parse_inventory(text: &str) -> Vec<ShopItem> {
+    ini::parse(text)
+        .iter()
+        .filter_map(|section| {
+            Some(ShopItem {
+                upgrade: Upgrade::from_id(&section.name)?,
+                name: section.get("name").unwrap_or(&section.name).to_string(),
+                price: section.parse("price").unwrap_or(10),
+                price_growth: section.parse("price_growth").unwrap_or(1.5),
+                max_purchases: section.parse("max_purchases").unwrap_or(0),
+                value: section.parse("value").unwrap_or(1.0),
+            })
+        })
+        .collect()
+}
+
+/// Upgrades bought during the current run, counted per shop item.
+pub struct Purchases {
+    counts: Vec<u32>,
+}
+
+impl Purchases {
+    pub fn new(items: &[ShopItem]) -> Purchases {
+        Purchases {
+            counts: vec![0; items.len()],
+        }
+    }
+
+    pub fn count(&self, index: usize) -> u32 {
+        self.counts[index]
+    }
+
+    pub fn record(&mut self, index: usize) {
+        self.counts[index] += 1;
+    }
+
+    /// Sum of `value` over every purchase of `upgrade`.
+    pub fn total(&self, items: &[ShopItem], upgrade: Upgrade) -> f32 {
+        items
+            .iter()
+            .zip(&self.counts)
+            .filter(|(item, _)| item.upgrade == upgrade)
+            .map(|(item, count)| item.value * *count as f32)
+            .sum()
+    }
+}
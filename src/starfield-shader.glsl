@@ -13,6 +13,7 @@ varying float iTime;
 
 uniform vec2 iResolution;
 uniform float direction_modifier;
+uniform vec3 tint;
 
 #define NUM_LAYERS 4.
 
@@ -83,5 +84,5 @@ void main()
         col += StarLayer(uv * scale + i * 453.2) * fade;
     }
 
-    gl_FragColor = vec4(col, 1.0);
+    gl_FragColor = vec4(col * tint, 1.0);
 }
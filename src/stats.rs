@@ -0,0 +1,340 @@
+//! Numbers about how each player did during a run, gathered from the
+//! playfield's `Event`s, and lifetime totals kept in the save under
+//! `lifetime.<name>`.
+
+use macroquad::prelude::*;
+
+use crate::leaderboard::format_duration;
+use crate::playfield::{BIG_ENEMY_SIZE, DEATH_PENALTY, Event, Playfield, TICK};
+use crate::save::SaveData;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EnemyKind {
+    Small,
+    Big,
+}
+
+impl EnemyKind {
+    pub const ALL: [EnemyKind; 2] = [EnemyKind::Small, EnemyKind::Big];
+
+    pub fn of(size: f32) -> EnemyKind {
+        if size > BIG_ENEMY_SIZE {
+            EnemyKind::Big
+        } else {
+            EnemyKind::Small
+        }
+    }
+
+    fn id(self) -> &'static str {
+        match self {
+            EnemyKind::Small => "small",
+            EnemyKind::Big => "big",
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            EnemyKind::Small => "Small enemy",
+            EnemyKind::Big => "Big enemy",
+        }
+    }
+}
+
+/// One player's run.
+#[derive(Clone, Debug, Default)]
+pub struct RunStats {
+    pub shots: u32,
+    /// Bullets that hit an enemy.
+    pub hits: u32,
+    /// Indexed like `EnemyKind::ALL`.
+    pub kills: [u32; 2],
+    /// In pixels, dashes included.
+    pub distance: f32,
+    /// Seconds alive.
+    pub time: f32,
+    /// Times the ship ran into something, shield or not.
+    pub hits_taken: u32,
+    /// Enemies that passed close by in pacifist mode.
+    pub near_misses: u32,
+    /// Seconds taken off the clock of a timed run by hits.
+    pub time_lost: f32,
+    /// What the ship last ran into. Once it's out of lives, that's what
+    /// finished it.
+    pub last_hit: Option<EnemyKind>,
+    pub died: bool,
+}
+
+impl RunStats {
+    pub fn accuracy(&self) -> Option<f32> {
+        (self.shots > 0).then(|| self.hits as f32 / self.shots as f32)
+    }
+
+    pub fn cause_of_death(&self) -> Option<EnemyKind> {
+        self.last_hit.filter(|_| self.died)
+    }
+}
+
+/// Gathers `RunStats` for every player in the current run.
+pub struct RunTracker {
+    /// Indexed like `Playfield::players`.
+    pub players: Vec<RunStats>,
+    positions: Vec<Vec2>,
+    last_tick: u64,
+}
+
+impl RunTracker {
+    pub fn new() -> RunTracker {
+        RunTracker {
+            players: vec![],
+            positions: vec![],
+            last_tick: 0,
+        }
+    }
+
+    /// Updates the stats with the `events` of the ticks simulated since the
+    /// last call.
+    pub fn update(&mut self, field: &Playfield, events: &[Event]) {
+        self.players
+            .resize_with(field.players.len(), RunStats::default);
+        for event in events {
+            match *event {
+                Event::Shot { player } => self.players[player].shots += 1,
+                Event::EnemyKilled {
+                    size, player, bomb, ..
+                } => {
+                    let stats = &mut self.players[player];
+                    stats.kills[EnemyKind::of(size) as usize] += 1;
+                    if !bomb {
+                        stats.hits += 1;
+                    }
+                }
+                Event::PlayerHit { player, size } => {
+                    let stats = &mut self.players[player];
+                    stats.hits_taken += 1;
+                    stats.last_hit = Some(EnemyKind::of(size));
+                }
+                Event::EnemyDamaged { player, .. } => self.players[player].hits += 1,
+                Event::NearMiss { player, .. } => self.players[player].near_misses += 1,
+                Event::TimeLost { player, .. } => self.players[player].time_lost += DEATH_PENALTY,
+                Event::Explosion { .. }
+                | Event::WaveCleared
+                | Event::BossSpawned
+                | Event::BossDefeated => {}
+            }
+        }
+
+        let elapsed = (field.tick - self.last_tick) as f32 * TICK;
+        self.last_tick = field.tick;
+        for (i, (player, stats)) in field.players.iter().zip(&mut self.players).enumerate() {
+            let position = vec2(player.ship.x, player.ship.y);
+            // Players who just joined have no previous position yet
+            if let Some(previous) = self.positions.get_mut(i) {
+                if player.alive() {
+                    stats.distance += position.distance(*previous);
+                    stats.time += elapsed;
+                }
+                *previous = position;
+            } else {
+                self.positions.push(position);
+            }
+            stats.died = !player.alive();
+        }
+    }
+
+    /// Adds the finished run to the lifetime totals.
+    pub fn record(&self, save: &mut SaveData) {
+        let mut add = |name: &str, value: u64| {
+            *save.stats.entry(format!("lifetime.{name}")).or_default() += value;
+        };
+        add("runs", 1);
+        for stats in &self.players {
+            add("shots", stats.shots.into());
+            add("hits", stats.hits.into());
+            for kind in EnemyKind::ALL {
+                add(
+                    &format!("kills.{}", kind.id()),
+                    stats.kills[kind as usize].into(),
+                );
+            }
+            add("distance", stats.distance as u64);
+            add("time", stats.time as u64);
+            if let Some(kind) = stats.cause_of_death() {
+                add(&format!("deaths.{}", kind.id()), 1);
+            }
+        }
+        let longest = self
+            .players
+            .iter()
+            .map(|stats| stats.time as u64)
+            .max()
+            .unwrap_or(0);
+        let record = save
+            .stats
+            .entry("lifetime.longest_run".to_string())
+            .or_default();
+        *record = (*record).max(longest);
+    }
+}
+
+fn lifetime(save: &SaveData, name: &str) -> u64 {
+    save.stats
+        .get(&format!("lifetime.{name}"))
+        .copied()
+        .unwrap_or(0)
+}
+
+fn format_accuracy(accuracy: Option<f32>) -> String {
+    accuracy.map_or("-".to_string(), |accuracy| {
+        format!("{:.0}%", accuracy * 100.0)
+    })
+}
+
+fn format_distance(pixels: f32) -> String {
+    format!("{:.1} km", pixels / 1000.0)
+}
+
+/// Rows of a label and one value per column, drawn in `font` from
+/// `top`.
+fn draw_table(rows: &[(String, Vec<String>)], top: f32, font: &Font, colors: &[Color]) {
+    let left = screen_width() / 2.0 - 220.0;
+    for (i, (label, values)) in rows.iter().enumerate() {
+        let y = top + i as f32 * 22.0;
+        let text = |text: &str, x: f32, color: Color| {
+            draw_text_ex(
+                text,
+                x,
+                y,
+                TextParams {
+                    font: Some(font),
+                    font_size: 12,
+                    color,
+                    ..Default::default()
+                },
+            );
+        };
+        text(&label.to_uppercase(), left, GRAY);
+        for (column, value) in values.iter().enumerate() {
+            let color = colors.get(column).copied().unwrap_or(WHITE);
+            text(
+                &value.to_uppercase(),
+                left + 260.0 + column as f32 * 120.0,
+                color,
+            );
+        }
+    }
+}
+
+/// The run's stats, one column per player, starting at `top`.
+pub fn draw_run(tracker: &RunTracker, field: &Playfield, top: f32, font: &Font) {
+    let column = |value: &dyn Fn(&RunStats) -> String| -> Vec<String> {
+        tracker.players.iter().map(value).collect()
+    };
+    let mut rows = vec![];
+    if field.players.len() > 1 {
+        let labels = field.players.iter().map(|player| player.label(2)).collect();
+        rows.push((String::new(), labels));
+    }
+    rows.push((
+        "Time survived".to_string(),
+        column(&|stats| format_duration(stats.time)),
+    ));
+    rows.push((
+        "Distance flown".to_string(),
+        column(&|stats| format_distance(stats.distance)),
+    ));
+    // Pacifists don't shoot, they dodge
+    if field.pacifist {
+        rows.push((
+            "Near misses".to_string(),
+            column(&|stats| stats.near_misses.to_string()),
+        ));
+    } else {
+        rows.push((
+            "Shots fired".to_string(),
+            column(&|stats| stats.shots.to_string()),
+        ));
+        rows.push(("Hits".to_string(), column(&|stats| stats.hits.to_string())));
+        rows.push((
+            "Accuracy".to_string(),
+            column(&|stats| format_accuracy(stats.accuracy())),
+        ));
+    }
+    for kind in EnemyKind::ALL {
+        rows.push((
+            format!("{} kills", kind.name()),
+            column(&|stats| stats.kills[kind as usize].to_string()),
+        ));
+    }
+    // Timed runs don't end in death, they lose time instead
+    if field.time_left.is_some() {
+        rows.push((
+            "Time lost".to_string(),
+            column(&|stats| format_duration(stats.time_lost)),
+        ));
+    } else {
+        rows.push((
+            "Cause of death".to_string(),
+            column(&|stats| {
+                stats
+                    .cause_of_death()
+                    .map_or("-", EnemyKind::name)
+                    .to_string()
+            }),
+        ));
+    }
+    let colors: Vec<Color> = field.players.iter().map(|player| player.color()).collect();
+    draw_table(&rows, top, font, &colors);
+}
+
+/// Lifetime totals, centred on screen.
+pub fn draw_lifetime(save: &SaveData, font: &Font) {
+    let top = screen_height() / 2.0 - 150.0;
+    let title = "LIFETIME STATS";
+    let title_dimensions = measure_text(title, Some(font), 40, 1.0);
+    draw_text_ex(
+        title,
+        screen_width() / 2.0 - title_dimensions.width / 2.0,
+        top - 40.0,
+        TextParams {
+            font: Some(font),
+            font_size: 40,
+            color: WHITE,
+            ..Default::default()
+        },
+    );
+
+    let shots = lifetime(save, "shots");
+    let accuracy = (shots > 0).then(|| lifetime(save, "hits") as f32 / shots as f32);
+    let mut rows = vec![
+        ("Runs".to_string(), lifetime(save, "runs").to_string()),
+        ("High score".to_string(), save.high_score.to_string()),
+        (
+            "Time played".to_string(),
+            format_duration(lifetime(save, "time") as f32),
+        ),
+        (
+            "Longest run".to_string(),
+            format_duration(lifetime(save, "longest_run") as f32),
+        ),
+        (
+            "Distance flown".to_string(),
+            format_distance(lifetime(save, "distance") as f32),
+        ),
+        ("Shots fired".to_string(), shots.to_string()),
+        ("Hits".to_string(), lifetime(save, "hits").to_string()),
+        ("Accuracy".to_string(), format_accuracy(accuracy)),
+    ];
+    for kind in EnemyKind::ALL {
+        let kills = lifetime(save, &format!("kills.{}", kind.id()));
+        rows.push((format!("{} kills", kind.name()), kills.to_string()));
+    }
+    for kind in EnemyKind::ALL {
+        let deaths = lifetime(save, &format!("deaths.{}", kind.id()));
+        rows.push((format!("Deaths to {}", kind.name()), deaths.to_string()));
+    }
+    let rows: Vec<(String, Vec<String>)> = rows
+        .into_iter()
+        .map(|(label, value)| (label, vec![value]))
+        .collect();
+    draw_table(&rows, top, font, &[]);
+}
@@ -0,0 +1,18 @@
+//! Time attack: the best score before the clock runs out. Waves follow each
+//! other without a shop in between, and a hit that would cost a life takes
+//! `playfield::DEATH_PENALTY` seconds off the clock instead.
+
+use crate::leaderboard::{TIME_ATTACK_2_BOARD, TIME_ATTACK_5_BOARD};
+
+/// Time limits to pick from, in minutes, with the board of each.
+pub const LIMITS: [(u32, &str); 2] = [(2, TIME_ATTACK_2_BOARD), (5, TIME_ATTACK_5_BOARD)];
+
+/// Clock of `limit`, an index into `LIMITS`, in seconds.
+pub fn seconds(limit: usize) -> f32 {
+    LIMITS[limit].0 as f32 * 60.0
+}
+
+/// Local and online board of `limit`.
+pub fn board(limit: usize) -> &'static str {
+    LIMITS[limit].1
+}
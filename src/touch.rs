@@ -0,0 +1,235 @@
+//! On-screen controls for phones and tablets, driving the first player.
+//! They stay hidden until the first touch. Menus built from `root_ui`
+//! buttons already work by tapping, since macroquad turns touches into
+//! mouse clicks.
+
+use macroquad::prelude::*;
+
+use crate::input::{Button, PlayerInput};
+
+const STICK_RADIUS: f32 = 60.0;
+/// How far from its target a dragged ship slows down, in seconds of travel.
+const DRAG_EASING: f32 = 0.1;
+const BUTTON_COLOR: Color = Color::new(1.0, 1.0, 1.0, 0.25);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TouchMode {
+    /// A joystick appears wherever the left thumb lands.
+    Joystick,
+    /// The ship follows the finger, keeping its distance from it.
+    Drag,
+}
+
+/// A round on-screen button, positioned relative to the screen size.
+struct TouchButton {
+    label: &'static str,
+    button: Button,
+    radius: f32,
+    /// Distance from the bottom-right corner.
+    offset: Vec2,
+}
+
+impl TouchButton {
+    fn center(&self) -> Vec2 {
+        vec2(screen_width(), screen_height()) - self.offset
+    }
+
+    fn contains(&self, position: Vec2) -> bool {
+        position.distance(self.center()) <= self.radius
+    }
+}
+
+const BUTTONS: [TouchButton; 3] = [
+    TouchButton {
+        label: "FIRE",
+        button: Button::Fire,
+        radius: 50.0,
+        offset: vec2(80.0, 90.0),
+    },
+    TouchButton {
+        label: "BOMB",
+        button: Button::Bomb,
+        radius: 35.0,
+        offset: vec2(190.0, 60.0),
+    },
+    TouchButton {
+        label: "DASH",
+        button: Button::Dash,
+        radius: 35.0,
+        offset: vec2(80.0, 210.0),
+    },
+];
+
+/// Small buttons along the top edge for pausing and switching modes.
+const PAUSE_OFFSET: f32 = -40.0;
+const MODE_OFFSET: f32 = 40.0;
+const SMALL_RADIUS: f32 = 25.0;
+
+fn top_button(offset: f32) -> Vec2 {
+    vec2(screen_width() / 2.0 + offset, 40.0)
+}
+
+pub struct TouchControls {
+    /// Set by the first touch, after which the controls are drawn.
+    pub active: bool,
+    pub mode: TouchMode,
+    /// Touch id, where it started and where it is now.
+    stick: Option<(u64, Vec2, Vec2)>,
+    /// Touch id and where it started.
+    drag: Option<(u64, Vec2)>,
+    drag_finger: Vec2,
+    /// Where the ship was when the drag started, picked up by `input`.
+    drag_ship: Option<Vec2>,
+    buttons: PlayerInput,
+    tapped: bool,
+    pause_tapped: bool,
+}
+
+impl TouchControls {
+    pub fn new() -> TouchControls {
+        TouchControls {
+            active: false,
+            mode: TouchMode::Joystick,
+            stick: None,
+            drag: None,
+            drag_finger: Vec2::ZERO,
+            drag_ship: None,
+            buttons: PlayerInput::default(),
+            tapped: false,
+            pause_tapped: false,
+        }
+    }
+
+    /// Reads this frame's touches.
+    pub fn update(&mut self) {
+        self.tapped = false;
+        self.pause_tapped = false;
+        self.buttons = PlayerInput::default();
+
+        for touch in touches() {
+            self.active = true;
+            let ended = matches!(touch.phase, TouchPhase::Ended | TouchPhase::Cancelled);
+            if touch.phase == TouchPhase::Started {
+                self.start(touch.id, touch.position);
+            }
+
+            if let Some((id, base, _)) = self.stick
+                && id == touch.id
+            {
+                self.stick = (!ended).then_some((id, base, touch.position));
+                continue;
+            }
+            if let Some((id, ..)) = self.drag
+                && id == touch.id
+            {
+                self.drag_finger = touch.position;
+                if ended {
+                    self.drag = None;
+                }
+                continue;
+            }
+            // Buttons react to any finger resting on them, so a thumb can
+            // slide from one to the next
+            if !ended {
+                for button in &BUTTONS {
+                    if button.contains(touch.position) {
+                        self.buttons.set(button.button, true);
+                    }
+                }
+            }
+        }
+    }
+
+    fn start(&mut self, id: u64, position: Vec2) {
+        self.tapped = true;
+        if position.distance(top_button(PAUSE_OFFSET)) <= SMALL_RADIUS {
+            self.pause_tapped = true;
+        } else if position.distance(top_button(MODE_OFFSET)) <= SMALL_RADIUS {
+            self.mode = match self.mode {
+                TouchMode::Joystick => TouchMode::Drag,
+                TouchMode::Drag => TouchMode::Joystick,
+            };
+        } else if !BUTTONS.iter().any(|button| button.contains(position)) {
+            match self.mode {
+                TouchMode::Joystick if position.x < screen_width() / 2.0 => {
+                    self.stick.get_or_insert((id, position, position));
+                }
+                TouchMode::Drag if self.drag.is_none() => {
+                    self.drag = Some((id, position));
+                    self.drag_finger = position;
+                    self.drag_ship = None;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Any new touch this frame, for "tap to continue" screens.
+    pub fn tapped(&self) -> bool {
+        self.tapped
+    }
+
+    pub fn pause_tapped(&self) -> bool {
+        self.pause_tapped
+    }
+
+    /// Input for the local ship. Its position on screen and speed are
+    /// needed to steer it after the finger in drag mode.
+    pub fn input(&mut self, ship: Option<(Vec2, f32)>) -> PlayerInput {
+        let mut input = self.buttons;
+        if let Some((_, base, position)) = self.stick {
+            input.set_stick((position - base) / STICK_RADIUS);
+        }
+        if let (Some((_, finger_start)), Some((ship, speed))) = (self.drag, ship) {
+            let target = *self.drag_ship.get_or_insert(ship) + self.drag_finger - finger_start;
+            input.steer(target - ship, speed * DRAG_EASING);
+        }
+        input
+    }
+
+    pub fn draw(&self) {
+        if !self.active {
+            return;
+        }
+        if let Some((_, base, position)) = self.stick {
+            let knob = base + (position - base).clamp_length_max(STICK_RADIUS);
+            draw_circle_lines(base.x, base.y, STICK_RADIUS, 3.0, BUTTON_COLOR);
+            draw_circle(knob.x, knob.y, STICK_RADIUS / 2.0, BUTTON_COLOR);
+        }
+        for button in &BUTTONS {
+            let center = button.center();
+            if self.buttons.held(button.button) {
+                draw_circle(center.x, center.y, button.radius, BUTTON_COLOR);
+            }
+            draw_circle_lines(center.x, center.y, button.radius, 3.0, BUTTON_COLOR);
+            let text_dimensions = measure_text(button.label, None, 20, 1.0);
+            draw_text(
+                button.label,
+                center.x - text_dimensions.width / 2.0,
+                center.y + text_dimensions.height / 2.0,
+                20.0,
+                WHITE,
+            );
+        }
+
+        let pause = top_button(PAUSE_OFFSET);
+        draw_circle_lines(pause.x, pause.y, SMALL_RADIUS, 2.0, BUTTON_COLOR);
+        draw_rectangle(pause.x - 8.0, pause.y - 10.0, 5.0, 20.0, WHITE);
+        draw_rectangle(pause.x + 3.0, pause.y - 10.0, 5.0, 20.0, WHITE);
+
+        let mode = top_button(MODE_OFFSET);
+        let label = match self.mode {
+            TouchMode::Joystick => "STK",
+            TouchMode::Drag => "DRG",
+        };
+        let text_dimensions = measure_text(label, None, 18, 1.0);
+        draw_circle_lines(mode.x, mode.y, SMALL_RADIUS, 2.0, BUTTON_COLOR);
+        draw_text(
+            label,
+            mode.x - text_dimensions.width / 2.0,
+            mode.y + text_dimensions.height / 2.0,
+            18.0,
+            WHITE,
+        );
+    }
+}
@@ -0,0 +1,251 @@
+//! The tutorial: an empty practice playfield with prompts that walk a new
+//! player through the controls one at a time, each waiting for the player
+//! to do it before moving on. Finishing it, or turning it down when it is
+//! offered, is kept in the save so it's only offered once.
+
+use macroquad::prelude::*;
+
+use crate::bindings::{Action, Bindings, Scope};
+use crate::gamepad::PadButton;
+use crate::playfield::{Event, Playfield, TICK};
+use crate::save::SaveData;
+
+/// Unlocked once the tutorial is finished or turned down.
+const UNLOCK: &str = "tutorial";
+/// Seconds of moving around before moving on.
+const MOVE_TIME: f32 = 1.5;
+/// Seconds the closing message stays up.
+const DONE_TIME: f32 = 4.0;
+const BOMB_TARGETS: usize = 5;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Step {
+    Move,
+    Shoot,
+    Dash,
+    Bomb,
+    Pause,
+    Resume,
+    Done,
+}
+
+impl Step {
+    const ALL: [Step; 7] = [
+        Step::Move,
+        Step::Shoot,
+        Step::Dash,
+        Step::Bomb,
+        Step::Pause,
+        Step::Resume,
+        Step::Done,
+    ];
+}
+
+pub struct Tutorial {
+    step: Step,
+    /// Seconds spent on the current step's goal.
+    progress: f32,
+    last_tick: u64,
+}
+
+impl Tutorial {
+    pub fn new() -> Tutorial {
+        Tutorial {
+            step: Step::Move,
+            progress: 0.0,
+            last_tick: 0,
+        }
+    }
+
+    /// The playfield to learn on: nothing spawns unless a step asks for it,
+    /// and nothing hurts.
+    pub fn playfield(seed: u64, size: Vec2) -> Playfield {
+        let mut field = Playfield::practice(seed, size);
+        field.invincible = true;
+        field
+    }
+
+    /// Moves on once the first player did what the current step asks, and
+    /// sends in the enemies it needs. Call after stepping `field`, with the
+    /// `events` of the ticks since the last call.
+    pub fn update(&mut self, field: &mut Playfield, events: &[Event]) {
+        let elapsed = (field.tick - self.last_tick) as f32 * TICK;
+        self.last_tick = field.tick;
+        let Some(player) = field.players.first_mut() else {
+            return;
+        };
+        let killed = |by_bomb: bool| {
+            events
+                .iter()
+                .any(|event| matches!(event, Event::EnemyKilled { bomb, .. } if *bomb == by_bomb))
+        };
+        match self.step {
+            Step::Move => {
+                if player.input.movement() != Vec2::ZERO {
+                    self.progress += elapsed;
+                }
+                if self.progress >= MOVE_TIME {
+                    self.advance();
+                }
+            }
+            Step::Shoot => {
+                if killed(false) {
+                    self.advance();
+                } else if field.squares.is_empty() {
+                    // A slow target, sent again if it gets away
+                    field.spawn_enemy((40.0, 48.0), (40.0, 50.0));
+                }
+            }
+            Step::Dash => {
+                if player.dash > 0.0 {
+                    self.advance();
+                }
+            }
+            Step::Bomb => {
+                if killed(true) {
+                    self.advance();
+                } else if field.squares.is_empty() {
+                    player.bombs = player.bombs.max(1);
+                    for _ in 0..BOMB_TARGETS {
+                        field.spawn_enemy((24.0, 40.0), (40.0, 60.0));
+                    }
+                }
+            }
+            // Paused and unpaused from the game's own pause screen
+            Step::Pause => {}
+            Step::Resume => self.advance(),
+            Step::Done => self.progress += elapsed,
+        }
+    }
+
+    /// Tells the tutorial the game was paused.
+    pub fn paused(&mut self) {
+        if self.step == Step::Pause {
+            self.advance();
+        }
+    }
+
+    pub fn finished(&self) -> bool {
+        self.step == Step::Done && self.progress >= DONE_TIME
+    }
+
+    fn advance(&mut self) {
+        let index = Step::ALL
+            .iter()
+            .position(|step| *step == self.step)
+            .unwrap();
+        self.step = Step::ALL[(index + 1).min(Step::ALL.len() - 1)];
+        self.progress = 0.0;
+    }
+
+    /// The current prompt, naming the first player's keys, or their gamepad
+    /// buttons when `gamepad` is set.
+    pub fn draw(&self, bindings: &Bindings, gamepad: bool, font: &Font) {
+        let control = |action: Action| {
+            let scope = if Action::GLOBAL.contains(&action) {
+                Scope::Global
+            } else {
+                Scope::Player(0)
+            };
+            match action.pad_button().filter(|_| gamepad) {
+                Some(button) => pad_name(button).to_string(),
+                None => format!("{:?}", bindings.key(scope, action)).to_uppercase(),
+            }
+        };
+        let (prompt, detail) = match self.step {
+            Step::Move if gamepad => ("MOVE WITH THE LEFT STICK".to_string(), ""),
+            Step::Move => (
+                format!(
+                    "MOVE WITH {} {} {} {}",
+                    control(Action::MoveLeft),
+                    control(Action::MoveRight),
+                    control(Action::MoveUp),
+                    control(Action::MoveDown)
+                ),
+                "",
+            ),
+            Step::Shoot => (
+                format!("PRESS {} TO SHOOT THE ENEMY", control(Action::Fire)),
+                "HOLD IT TO KEEP FIRING",
+            ),
+            Step::Dash => (
+                format!("PRESS {} TO DASH", control(Action::Dash)),
+                "NOTHING CAN HIT YOU MID DASH",
+            ),
+            Step::Bomb => (
+                format!("PRESS {} TO DROP A BOMB", control(Action::Bomb)),
+                "BOMBS CLEAR THE SCREEN, THE SHOP SELLS MORE",
+            ),
+            Step::Pause => (format!("PRESS {} TO PAUSE", control(Action::Pause)), ""),
+            Step::Resume => (
+                "PICK RESUME TO CARRY ON".to_string(),
+                "OR QUIT TO THE MENU FROM HERE ANY TIME",
+            ),
+            Step::Done => (
+                "YOU ARE READY!".to_string(),
+                "CLEAR WAVES TO EARN CREDITS FOR THE SHOP",
+            ),
+        };
+
+        let text = |text: &str, y: f32, font_size: u16, color: Color| {
+            let dimensions = measure_text(text, Some(font), font_size, 1.0);
+            draw_text_ex(
+                text,
+                screen_width() / 2.0 - dimensions.width / 2.0,
+                y,
+                TextParams {
+                    font: Some(font),
+                    font_size,
+                    color,
+                    ..Default::default()
+                },
+            );
+        };
+        // The pause menu fills the middle of the screen
+        let top = if self.step == Step::Resume {
+            screen_height() - 100.0
+        } else {
+            screen_height() / 3.0
+        };
+        // The pause and resume prompts are one step to the player
+        let number = Step::ALL
+            .iter()
+            .position(|step| *step == self.step)
+            .unwrap()
+            .min(Step::ALL.len() - 3);
+        if self.step != Step::Done {
+            text(
+                &format!("TUTORIAL {} OF {}", number + 1, Step::ALL.len() - 2),
+                top - 40.0,
+                14,
+                GRAY,
+            );
+        }
+        text(&prompt, top, 24, YELLOW);
+        text(detail, top + 32.0, 14, WHITE);
+    }
+}
+
+fn pad_name(button: PadButton) -> &'static str {
+    match button {
+        PadButton::South => "A",
+        PadButton::East => "B",
+        PadButton::West => "X",
+        PadButton::North => "Y",
+        PadButton::Start => "START",
+        PadButton::Select => "SELECT",
+        PadButton::DPadUp | PadButton::DPadDown | PadButton::DPadLeft | PadButton::DPadRight => {
+            "THE D-PAD"
+        }
+    }
+}
+
+/// Whether the tutorial should still be offered.
+pub fn offer(save: &SaveData) -> bool {
+    !save.unlocks.contains(UNLOCK)
+}
+
+/// Stops offering the tutorial, once it's been played or turned down.
+pub fn remember(save: &mut SaveData) {
+    save.unlocks.insert(UNLOCK.to_string());
+}
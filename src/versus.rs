@@ -0,0 +1,82 @@
+//! Split-screen versus: each player gets their own `Playfield`, and kills on
+//! one side send garbage enemies to the other.
+
+use macroquad::prelude::*;
+
+use crate::input::PlayerInput;
+use crate::playfield::{BIG_ENEMY_SIZE, Event, Playfield};
+
+const VERSUS_LIVES: u32 = 3;
+/// Small kills needed to send one garbage enemy; big ones send one each.
+const KILLS_PER_GARBAGE: u32 = 3;
+
+pub struct Versus {
+    pub fields: [Playfield; 2],
+    garbage_meters: [u32; 2],
+}
+
+impl Versus {
+    /// Both sides share a seed so they start out facing the same enemies.
+    pub fn new(seed: u64, size: Vec2) -> Versus {
+        let mut fields = [Playfield::new(seed, size), Playfield::new(seed, size)];
+        for (slot, field) in fields.iter_mut().enumerate() {
+            field.join_as(slot);
+            field.players[0].lives = VERSUS_LIVES;
+        }
+        Versus {
+            fields,
+            garbage_meters: [0; 2],
+        }
+    }
+
+    /// Size of one side of the split screen.
+    pub fn resize(&mut self, size: Vec2) {
+        for field in &mut self.fields {
+            field.size = size;
+        }
+    }
+
+    pub fn step(&mut self, inputs: &[PlayerInput], events: &mut [Vec<Event>; 2]) {
+        for (side, events) in events.iter_mut().enumerate() {
+            let start = events.len();
+            let field = &mut self.fields[side];
+            field.step(inputs, events);
+            // Waves roll straight on, there is no shop in versus
+            if field.wave_cleared {
+                field.start_next_wave();
+            }
+
+            let mut garbage = 0;
+            for event in &events[start..] {
+                if let Event::EnemyKilled { size, .. } = event {
+                    if *size > BIG_ENEMY_SIZE {
+                        garbage += 1;
+                    } else {
+                        self.garbage_meters[side] += 1;
+                    }
+                }
+            }
+            garbage += self.garbage_meters[side] / KILLS_PER_GARBAGE;
+            self.garbage_meters[side] %= KILLS_PER_GARBAGE;
+            self.fields[1 - side].incoming_garbage += garbage;
+        }
+    }
+
+    /// The round ends as soon as one side is out. Returns the winning side,
+    /// or `None` while both are still alive. A simultaneous knockout goes to
+    /// the higher score.
+    pub fn winner(&self) -> Option<usize> {
+        match (self.fields[0].game_over(), self.fields[1].game_over()) {
+            (false, false) => None,
+            (false, true) => Some(0),
+            (true, false) => Some(1),
+            (true, true) => {
+                if self.fields[1].best_score() > self.fields[0].best_score() {
+                    Some(1)
+                } else {
+                    Some(0)
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,335 @@
+//! Drawing and sound for a `Playfield`. Everything here is cosmetic and
+//! never feeds back into the simulation, so it can be skipped or replayed
+//! freely when the netcode re-simulates.
+
+use macroquad::audio::{PlaySoundParams, Sound, play_sound};
+use macroquad::experimental::animation::{AnimatedSprite, Animation};
+use macroquad::prelude::*;
+use macroquad_particles::{Emitter, EmitterConfig};
+
+use crate::player::{CHAIN_WINDOW, MAX_PLAYERS};
+use crate::playfield::{BIG_ENEMY_SIZE, Event, Playfield};
+use crate::popups::Popups;
+use crate::settings::Settings;
+use crate::{Resources, particle_exhaust, particle_explosion};
+
+/// Furthest the playfield moves at full shake, in pixels.
+const MAX_SHAKE: f32 = 12.0;
+/// Shake that wears off per second.
+const SHAKE_DECAY: f32 = 1.5;
+
+pub struct PlayfieldView {
+    bullet_sprite: AnimatedSprite,
+    enemy_small_sprite: AnimatedSprite,
+    enemy_big_sprite: AnimatedSprite,
+    ship_sprites: Vec<AnimatedSprite>,
+    exhausts: Vec<Emitter>,
+    explosions: Vec<(Emitter, Vec2)>,
+    popups: Popups,
+    /// From 0 to 1, the offset grows with its square so small knocks stay
+    /// subtle.
+    shake: f32,
+}
+
+impl PlayfieldView {
+    pub fn new() -> PlayfieldView {
+        let mut bullet_sprite = AnimatedSprite::new(
+            16,
+            16,
+            &[
+                Animation {
+                    name: "bullet".to_string(),
+                    row: 0,
+                    frames: 2,
+                    fps: 12,
+                },
+                Animation {
+                    name: "bolt".to_string(),
+                    row: 1,
+                    frames: 2,
+                    fps: 12,
+                },
+            ],
+            true,
+        );
+        bullet_sprite.set_animation(1);
+
+        let enemy_small_sprite = AnimatedSprite::new(
+            17,
+            16,
+            &[Animation {
+                name: "enemy_small".to_string(),
+                row: 0,
+                frames: 2,
+                fps: 12,
+            }],
+            true,
+        );
+
+        let enemy_big_sprite = AnimatedSprite::new(
+            32,
+            32,
+            &[Animation {
+                name: "enemy_big".to_string(),
+                row: 0,
+                frames: 2,
+                fps: 12,
+            }],
+            true,
+        );
+
+        PlayfieldView {
+            bullet_sprite,
+            enemy_small_sprite,
+            enemy_big_sprite,
+            ship_sprites: (0..MAX_PLAYERS).map(|_| ship_sprite()).collect(),
+            exhausts: (0..MAX_PLAYERS)
+                .map(|_| {
+                    Emitter::new(EmitterConfig {
+                        amount: 64,
+                        ..particle_exhaust()
+                    })
+                })
+                .collect(),
+            explosions: vec![],
+            popups: Popups::new(),
+            shake: 0.0,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.explosions.clear();
+        self.popups.clear();
+        self.shake = 0.0;
+    }
+
+    /// Plays sounds, shakes the screen and spawns particles and popups for
+    /// simulation events.
+    pub fn handle(&mut self, events: &[Event], resources: &Resources, settings: &Settings) {
+        self.popups.handle(events, &resources.font);
+        let play = |sound: &Sound| {
+            play_sound(
+                sound,
+                PlaySoundParams {
+                    looped: false,
+                    volume: settings.sfx(),
+                },
+            )
+        };
+        let mut shake = 0.0;
+        for event in events {
+            match event {
+                Event::Shot { .. } => play(&resources.sound_laser),
+                Event::Explosion { position, size } => {
+                    self.explosions.push((
+                        Emitter::new(EmitterConfig {
+                            amount: size.round() as u32 * 4,
+                            texture: Some(resources.explosion_texture.clone()),
+                            lifetime: size * 0.0133,
+                            ..particle_explosion()
+                        }),
+                        *position,
+                    ));
+                    play(&resources.sound_explosion);
+                }
+                Event::EnemyKilled { bomb: true, .. } => shake += 0.2,
+                Event::PlayerHit { .. } => shake += 0.5,
+                Event::BossDefeated => shake += 1.0,
+                Event::EnemyKilled { .. }
+                | Event::EnemyDamaged { .. }
+                | Event::WaveCleared
+                | Event::BossSpawned
+                | Event::TimeLost { .. }
+                | Event::NearMiss { .. } => {}
+            }
+        }
+        self.shake = (self.shake + shake * settings.screen_shake).min(1.0);
+    }
+
+    /// Advances animations to match the players' latest input.
+    pub fn update(&mut self, field: &Playfield) {
+        for player in &field.players {
+            let sprite = &mut self.ship_sprites[player.slot];
+            let movement = player.input.movement();
+            if movement.x > 0.0 {
+                sprite.set_animation(2);
+            } else if movement.x < 0.0 {
+                sprite.set_animation(1);
+            } else {
+                sprite.set_animation(0);
+            }
+        }
+        for sprite in &mut self.ship_sprites {
+            sprite.update();
+        }
+        self.bullet_sprite.update();
+        self.enemy_small_sprite.update();
+        self.enemy_big_sprite.update();
+        self.explosions
+            .retain(|(explosion, _)| explosion.config.emitting);
+        self.popups.update(get_frame_time());
+    }
+
+    /// Draws `field` with its top-left corner at `origin`.
+    pub fn draw(&mut self, field: &Playfield, origin: Vec2, resources: &Resources) {
+        // Shake wears off as frames are drawn, so it settles on a frozen
+        // field too
+        let origin = origin
+            + vec2(rand::gen_range(-1.0, 1.0), rand::gen_range(-1.0, 1.0))
+                * self.shake.powi(2)
+                * MAX_SHAKE;
+        self.shake = (self.shake - SHAKE_DECAY * get_frame_time()).max(0.0);
+        for player in field.players.iter().filter(|player| player.alive()) {
+            let ship = &player.ship;
+            let frame = self.ship_sprites[player.slot].frame();
+            // Blink while invulnerable
+            if player.invulnerable <= 0.0 || ((get_time() * 10.0) as u32).is_multiple_of(2) {
+                draw_texture_ex(
+                    &resources.ship_texture,
+                    origin.x + ship.x - frame.dest_size.x,
+                    origin.y + ship.y - frame.dest_size.y,
+                    player.color(),
+                    DrawTextureParams {
+                        dest_size: Some(frame.dest_size * 2.0),
+                        source: Some(frame.source_rect),
+                        ..Default::default()
+                    },
+                );
+            }
+            if player.shield > 0 {
+                draw_circle_lines(
+                    origin.x + ship.x,
+                    origin.y + ship.y,
+                    ship.size,
+                    2.0,
+                    SKYBLUE,
+                );
+            }
+            self.exhausts[player.slot].draw(origin + vec2(ship.x, ship.y + ship.size / 2.0));
+        }
+
+        for square in &field.squares {
+            let (texture, enemy_frame) = match square.size {
+                size if size > BIG_ENEMY_SIZE => {
+                    (&resources.enemy_big_texture, self.enemy_big_sprite.frame())
+                }
+                _ => (
+                    &resources.enemy_small_texture,
+                    self.enemy_small_sprite.frame(),
+                ),
+            };
+            draw_texture_ex(
+                texture,
+                origin.x + square.x - square.size / 2.0,
+                origin.y + square.y - square.size / 2.0,
+                WHITE,
+                DrawTextureParams {
+                    dest_size: Some(vec2(square.size, square.size)),
+                    source: Some(enemy_frame.source_rect),
+                    ..Default::default()
+                },
+            );
+        }
+
+        if let Some(boss) = &field.boss {
+            draw_texture_ex(
+                &resources.enemy_big_texture,
+                origin.x + boss.x - boss.size / 2.0,
+                origin.y + boss.y - boss.size / 2.0,
+                Color::new(1.0, 0.6, 0.6, 1.0),
+                DrawTextureParams {
+                    dest_size: Some(vec2(boss.size, boss.size)),
+                    source: Some(self.enemy_big_sprite.frame().source_rect),
+                    ..Default::default()
+                },
+            );
+            // Health bar along the bottom of the playfield, clear of the
+            // scores
+            let fill = boss.health as f32 / field.boss_max_health.max(1) as f32;
+            let width = field.size.x * 0.5;
+            let x = origin.x + field.size.x / 2.0 - width / 2.0;
+            let y = origin.y + field.size.y - 40.0;
+            draw_rectangle(x, y, width * fill, 10.0, RED);
+            draw_rectangle_lines(x, y, width, 10.0, 2.0, WHITE);
+        }
+
+        let bullet_frame = self.bullet_sprite.frame();
+        for (bullet, _) in &field.bullets {
+            draw_texture_ex(
+                &resources.bullet_texture,
+                origin.x + bullet.x - bullet.size / 2.0,
+                origin.y + bullet.y - bullet.size / 2.0,
+                WHITE,
+                DrawTextureParams {
+                    dest_size: Some(vec2(bullet.size, bullet.size)),
+                    source: Some(bullet_frame.source_rect),
+                    ..Default::default()
+                },
+            )
+        }
+
+        for (explosion, coords) in self.explosions.iter_mut() {
+            explosion.draw(origin + *coords)
+        }
+        self.popups.draw(origin, &resources.font);
+    }
+
+    /// Per-player score lines in the top-left corner of the playfield, with
+    /// the chain multiplier and the time left to extend it.
+    pub fn draw_scores(&self, field: &Playfield, origin: Vec2) {
+        for (i, player) in field.players.iter().enumerate() {
+            let label = player.label(field.players.len());
+            let y = origin.y + 35.0 + i as f32 * 50.0;
+            let score_text = format!("{label}Score {}", player.score);
+            draw_text(&score_text, origin.x + 10.0, y, 25.0, player.color());
+            if player.chain > 0 {
+                let x = origin.x + 25.0 + measure_text(&score_text, None, 25, 1.0).width;
+                let multiplier_text = format!("x{}", player.multiplier());
+                draw_text(&multiplier_text, x, y, 25.0, YELLOW);
+                let bar_x = x + measure_text(&multiplier_text, None, 25, 1.0).width + 8.0;
+                let fill = (player.chain_timer / CHAIN_WINDOW).clamp(0.0, 1.0);
+                draw_rectangle(bar_x, y - 14.0, 60.0 * fill, 8.0, YELLOW);
+                draw_rectangle_lines(bar_x, y - 14.0, 60.0, 8.0, 1.0, YELLOW);
+            }
+            draw_text(
+                format!(
+                    "Lives {}  Shield {}  Bombs {}",
+                    player.lives, player.shield, player.bombs
+                )
+                .as_str(),
+                origin.x + 10.0,
+                y + 25.0,
+                25.0,
+                player.color(),
+            );
+        }
+    }
+}
+
+fn ship_sprite() -> AnimatedSprite {
+    AnimatedSprite::new(
+        16,
+        24,
+        &[
+            Animation {
+                name: "idle".to_string(),
+                row: 0,
+                frames: 2,
+                fps: 12,
+            },
+            Animation {
+                name: "left".to_string(),
+                row: 2,
+                frames: 2,
+                fps: 12,
+            },
+            Animation {
+                name: "right".to_string(),
+                row: 4,
+                frames: 2,
+                fps: 12,
+            },
+        ],
+        true,
+    )
+}